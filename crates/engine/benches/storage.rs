@@ -0,0 +1,140 @@
+// Compares the sparse-set `World` storage with the previous layout, where each
+// component type lived in its own `HashMap<Entity, T>`.
+//
+// Run with `cargo bench -p engine --bench storage`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cgmath::vec3;
+use hashbrown::HashMap;
+
+use engine::world::components::{Component, TransformComponent};
+use engine::world::entity::Entity;
+use engine::world::world::World;
+
+const ENTITY_COUNTS: [usize; 2] = [10_000, 100_000];
+const ITERATIONS: u32 = 20;
+
+#[derive(Clone, Copy)]
+struct Velocity {
+    x: f32,
+    y: f32
+}
+
+impl Component for Velocity {}
+
+// Every other entity has a velocity, so joins have to skip half of the transforms.
+fn has_velocity(i: usize) -> bool {
+    i.is_multiple_of(2)
+}
+
+struct HashMapLayout {
+    transforms: HashMap<Entity, TransformComponent>,
+    velocities: HashMap<Entity, Velocity>
+}
+
+impl HashMapLayout {
+    fn spawn(count: usize) -> Self {
+        let mut layout = HashMapLayout {
+            transforms: HashMap::new(),
+            velocities: HashMap::new()
+        };
+
+        for i in 0..count {
+            let entity = Entity::new(i as u32, 0);
+            layout.transforms.insert(entity, TransformComponent::new());
+            if has_velocity(i) {
+                layout.velocities.insert(entity, Velocity { x: 1.0, y: 0.5 });
+            }
+        }
+
+        layout
+    }
+
+    fn iterate(&mut self) {
+        for transform_comp in self.transforms.values_mut() {
+            transform_comp.transform.compute_model_matrix();
+        }
+    }
+
+    fn join(&mut self) {
+        for (entity, velocity) in self.velocities.iter() {
+            if let Some(transform_comp) = self.transforms.get_mut(entity) {
+                let position = *transform_comp.transform.get_local_position();
+                transform_comp.transform.set_local_position(position + vec3(velocity.x, velocity.y, 0.0));
+            }
+        }
+    }
+}
+
+fn spawn_world(count: usize) -> World {
+    let mut world = World::new();
+    world.register_component::<TransformComponent>();
+    world.register_component::<Velocity>();
+
+    for i in 0..count {
+        let entity = world.new_entity();
+        world.add_component(entity, TransformComponent::new());
+        if has_velocity(i) {
+            world.add_component(entity, Velocity { x: 1.0, y: 0.5 });
+        }
+    }
+
+    world
+}
+
+fn iterate_world(world: &mut World) {
+    for mut transform_comp in world.query::<&mut TransformComponent>() {
+        transform_comp.transform.compute_model_matrix();
+    }
+}
+
+fn join_world(world: &mut World) {
+    for (mut transform_comp, velocity) in world.query::<(&mut TransformComponent, &Velocity)>() {
+        let position = *transform_comp.transform.get_local_position();
+        transform_comp.transform.set_local_position(position + vec3(velocity.x, velocity.y, 0.0));
+    }
+}
+
+fn measure<F: FnMut()>(mut f: F) -> Duration {
+    f();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn report(name: &str, count: usize, hashmap: Duration, sparse_set: Duration) {
+    println!(
+        "{:<8} {:>7} entities | hashmap {:>10.3?} | sparse set {:>10.3?} | x{:.2}",
+        name,
+        count,
+        hashmap,
+        sparse_set,
+        hashmap.as_secs_f64() / sparse_set.as_secs_f64()
+    );
+}
+
+fn main() {
+    for count in ENTITY_COUNTS {
+        let hashmap = measure(|| { black_box(HashMapLayout::spawn(count)); });
+        let sparse_set = measure(|| { black_box(spawn_world(count)); });
+        report("spawn", count, hashmap, sparse_set);
+
+        let mut layout = HashMapLayout::spawn(count);
+        let mut world = spawn_world(count);
+
+        let hashmap = measure(|| layout.iterate());
+        let sparse_set = measure(|| iterate_world(&mut world));
+        report("iterate", count, hashmap, sparse_set);
+
+        let hashmap = measure(|| layout.join());
+        let sparse_set = measure(|| join_world(&mut world));
+        report("join", count, hashmap, sparse_set);
+
+        black_box((&layout.transforms, &world));
+    }
+}
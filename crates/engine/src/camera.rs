@@ -2,6 +2,7 @@ use cgmath;
 use cgmath::{vec3, Matrix4, Vector3};
 
 use crate::core::frame_context::FrameContext;
+use crate::math::shapes::Rect;
 use crate::world::components::TransformComponent;
 use crate::world::entity::Entity;
 
@@ -38,6 +39,18 @@ impl Camera {
         scale * translation
     }
 
+    // World-space area shown on screen, e.g. to skip sprites that cannot be seen.
+    pub fn visible_rect(&self, width: u32, height: u32) -> Rect {
+        let visible_width = width as f32 / self.zoom;
+        let visible_height = height as f32 / self.zoom;
+        Rect::new(
+            self.position.x - visible_width / 2.0,
+            self.position.y - visible_height / 2.0,
+            visible_width,
+            visible_height
+        )
+    }
+
     pub fn update(&mut self, ctx: &FrameContext) {
         let scroll_delta = ctx.input.get_scroll_delta();
         if scroll_delta != 0.0 {
@@ -46,9 +59,12 @@ impl Camera {
         }
 
         if let Some(target_entity) = self.target {
+            // Follows where the target is drawn, which lags behind its latest
+            // state when its transform is interpolated.
             if let Some(transform_comp) = ctx.world.get_component::<TransformComponent>(target_entity) {
-                self.position.x = transform_comp.transform.get_global_position().x;
-                self.position.y = transform_comp.transform.get_global_position().y;
+                let drawn_position = transform_comp.transform.get_model_matrix().w;
+                self.position.x = drawn_position.x;
+                self.position.y = drawn_position.y;
             }
         }
     }
@@ -1,48 +1,51 @@
 use cgmath::{ortho, Vector3, Matrix4};
 use glfw::{Context, Key};
 
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
 
 use crate::c_str;
 use crate::core::input::InputHandler;
 use crate::core::time::Time;
 use crate::glutils::{
+    framebuffer::{self, Framebuffer},
     shader::Shader
 };
 
 use crate::core::frame_context::FrameContext;
+use crate::core::scene::{SceneContext, SceneStack};
 use crate::camera::Camera;
-use crate::graphics::animation::AnimationComponent;
-use crate::graphics::managers::{AnimationManager, SpritesheetManager};
-use crate::graphics::sprite::SpriteRendererComponent;
-use crate::world::components::{Parent, TransformComponent};
-use crate::world::system::{AnimationSystem, SpriteRenderSystem, System, TransformSystem};
+use crate::core::plugin::{DefaultPlugins, Plugin};
+use crate::graphics::postprocess::PostProcessStack;
+use crate::world::commands::Commands;
+use crate::world::prefab::{ComponentRegistry, PrefabManager};
+use crate::world::snapshot::SnapshotRegistry;
+use crate::world::schedule::{IntoSystemConfig, Schedule, Stage};
 use crate::world::world::World;
 
-pub struct Application {
+// Sprites are ordered on the CPU (see `DrawOrder`), the projection only has
+// to keep every z in view, zoom included.
+const DEPTH_RANGE: f32 = 10000.0;
+
+// Everything tied to the OS window and the GL context. Headless applications
+// have none of it.
+struct WindowBackend {
     glfw: glfw::Glfw,
     window: glfw::Window,
     events: Receiver<(f64, glfw::WindowEvent)>,
-    pub camera: Camera,
-    time: Time,
-    shader: Shader,
-    pub world: World,
-    pub systems: Vec<Box<dyn System>>,
-    input: InputHandler,
-    pub spritesheet_manager: SpritesheetManager,
-    pub animation_manager: AnimationManager,
-    pub width: u32,
-    pub height: u32
+    shader: Shader
 }
 
-impl Application {
-    pub fn new(width: u32, height: u32, title: &str) -> Self {
+impl WindowBackend {
+    fn new(width: u32, height: u32, title: &str, visible: bool) -> Self {
         // glfw: initialize and configure
         let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
         glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
         glfw.window_hint(glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
         #[cfg(target_os="macos")]
         glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
+        glfw.window_hint(glfw::WindowHint::Visible(visible));
 
         // glfw window creation
         let (mut window, events) = glfw.create_window(width, height, title, glfw::WindowMode::Windowed)
@@ -79,97 +82,426 @@ impl Application {
         }
 
         let shader = Shader::new("shaders/shader.vs", "shaders/shader.fs");
-        
+
+        WindowBackend {
+            glfw,
+            window,
+            events,
+            shader
+        }
+    }
+}
+
+pub struct ApplicationBuilder {
+    width: u32,
+    height: u32,
+    title: String,
+    headless: bool,
+    hidden: bool,
+    fixed_update_rate: Option<f32>,
+    max_fixed_steps: Option<u32>,
+    plugins: Vec<Box<dyn Plugin>>,
+    plugin_names: Vec<&'static str>,
+    // Set while `build` runs the plugins: plugins added from there are built
+    // right away, before the rest of the plugin that adds them.
+    building_plugins: bool,
+    world: World,
+    schedule: Schedule
+}
+
+impl Default for ApplicationBuilder {
+    fn default() -> Self {
+        ApplicationBuilder::new()
+    }
+}
+
+#[allow(dead_code)]
+impl ApplicationBuilder {
+    pub fn new() -> Self {
+        // Prefab and snapshot support is part of every world; plugins register
+        // their own components with them.
         let mut world = World::new();
+        world.insert_resource(PrefabManager::new());
+        world.insert_resource(ComponentRegistry::new());
+        world.insert_resource(SnapshotRegistry::new());
+
+        ApplicationBuilder {
+            width: 800,
+            height: 600,
+            title: "Elyria".to_string(),
+            headless: false,
+            hidden: false,
+            fixed_update_rate: None,
+            max_fixed_steps: None,
+            plugins: Vec::new(),
+            plugin_names: Vec::new(),
+            building_plugins: false,
+            world,
+            schedule: Schedule::new()
+        }
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    // No window, no GL context and no render stage: systems, time and input
+    // only. Input can be simulated through `Application::input_mut`.
+    pub fn headless(mut self) -> Self {
+        self.headless = true;
+        self
+    }
+
+    // A real GL context behind a window that is never shown, e.g. to render
+    // golden images on a CI machine with a software GL.
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+
+    pub fn fixed_update_rate(mut self, rate: f32) -> Self {
+        self.fixed_update_rate = Some(rate);
+        self
+    }
+
+    pub fn max_fixed_steps(mut self, max_fixed_steps: u32) -> Self {
+        self.max_fixed_steps = Some(max_fixed_steps);
+        self
+    }
+
+    // Plugins are built in the order they were added, once the window (if
+    // any) exists. Adding the same plugin type again is ignored, so a plugin
+    // can add the ones it depends on from its own `build`.
+    pub fn add_plugin<P: Plugin + 'static>(&mut self, plugin: P) -> &mut Self {
+        let name = std::any::type_name::<P>();
+        if self.plugin_names.contains(&name) {
+            return self;
+        }
+
+        self.plugin_names.push(name);
+        if self.building_plugins {
+            plugin.build(self);
+        } else {
+            self.plugins.push(Box::new(plugin));
+        }
+        self
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.headless
+    }
 
-        world.register_component::<Parent>();
-        world.register_component::<TransformComponent>();
-        world.register_component::<SpriteRendererComponent>();
-        world.register_component::<AnimationComponent>();
+    pub fn world(&self) -> &World {
+        &self.world
+    }
 
-        let spritesheet_manager = SpritesheetManager::new();
-        let animation_manager = AnimationManager::new();
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn add_system<S: IntoSystemConfig>(&mut self, stage: Stage, system: S) -> &mut Self {
+        self.schedule.add_system(stage, system);
+        self
+    }
+
+    pub fn build(mut self) -> Application {
+        let backend = (!self.headless).then(|| WindowBackend::new(self.width, self.height, &self.title, !self.hidden));
+
+        self.building_plugins = true;
+        for plugin in std::mem::take(&mut self.plugins) {
+            plugin.build(&mut self);
+        }
 
         let mut input = InputHandler::new();
-        let (xpos, ypos) = window.get_cursor_pos();
-        input.last_x = xpos as f32;
-        input.last_y = ypos as f32;
+        if let Some(backend) = &backend {
+            let (xpos, ypos) = backend.window.get_cursor_pos();
+            input.last_x = xpos as f32;
+            input.last_y = ypos as f32;
+        }
 
-        let mut systems: Vec<Box<dyn System>> = Vec::new();
-        systems.push(Box::new(TransformSystem));
-        systems.push(Box::new(AnimationSystem));
-        systems.push(Box::new(SpriteRenderSystem));
+        let mut time = Time::new();
+        if let Some(rate) = self.fixed_update_rate {
+            time.set_fixed_update_rate(rate);
+        }
+        if let Some(max_fixed_steps) = self.max_fixed_steps {
+            time.set_max_fixed_steps(max_fixed_steps);
+        }
 
-        Self {
-            glfw,
-            window,
-            events,
+        let commands = Commands::new(&self.world);
+
+        Application {
+            backend,
             camera: Camera { position: Vector3::new(0.0, 0.0, 3.0), ..Camera::default()},
-            time: Time::new(),
-            shader,
-            world,
-            systems,
+            time,
+            world: self.world,
+            commands,
+            schedule: self.schedule,
+            scenes: SceneStack::new(),
             input,
-            spritesheet_manager,
-            animation_manager,
-            width,
-            height
+            width: self.width,
+            height: self.height,
+            clock: Instant::now(),
+            stepped_time: Duration::ZERO,
+            exit_requested: false,
+            screenshot_path: None
         }
     }
+}
 
+pub struct Application {
+    backend: Option<WindowBackend>,
+    pub camera: Camera,
+    time: Time,
+    pub world: World,
+    commands: Commands,
+    pub schedule: Schedule,
+    pub scenes: SceneStack,
+    input: InputHandler,
+    pub width: u32,
+    pub height: u32,
+    // Real-time clock of headless applications, which have no GLFW timer.
+    clock: Instant,
+    // Sum of the `step` deltas, the clock of stepped applications.
+    stepped_time: Duration,
+    exit_requested: bool,
+    // Saved from the window at the end of the next frame.
+    screenshot_path: Option<PathBuf>
+}
+
+impl Application {
+    pub fn new(width: u32, height: u32, title: &str) -> Self {
+        let mut builder = ApplicationBuilder::new().size(width, height).title(title);
+        builder.add_plugin(DefaultPlugins);
+        builder.build()
+    }
+
+    // Runs frames in real time until the window is closed, a scene quits or
+    // `exit` is called.
     pub fn run(&mut self) {
-        while !self.window.should_close() {
-            // Per-frame time logic
-            self.time.update(self.glfw.get_time());
-
-            // Events and input
-            self.process_events();
-            self.process_input();
-
-            let mut frame_context = FrameContext {
-                time: &self.time,
-                input: &self.input,
-                world: &mut self.world,
-                spritesheet_manager: &mut self.spritesheet_manager,
-                animation_manager: &mut self.animation_manager
+        if !self.prepare() {
+            return;
+        }
+
+        while !self.should_exit() {
+            let frame_start = Instant::now();
+
+            let now = match &self.backend {
+                Some(backend) => backend.glfw.get_time(),
+                None => self.clock.elapsed().as_secs_f64()
             };
+            self.frame(now);
+
+            // Without vsync to wait on, headless loops would spin: pace them
+            // to the fixed update rate instead.
+            if self.backend.is_none() {
+                let frame_budget = Duration::from_secs_f32(self.time.fixed_delta_time());
+                if let Some(remaining) = frame_budget.checked_sub(frame_start.elapsed()) {
+                    std::thread::sleep(remaining);
+                }
+            }
+        }
+
+        self.exit_scenes();
+    }
+
+    // Runs a single frame as if `delta_time` seconds had passed since the
+    // previous one, whatever the real clock says. Meant for tests and tools
+    // that drive the application themselves; do not mix with `run`.
+    pub fn step(&mut self, delta_time: f32) {
+        let Ok(delta_time) = Duration::try_from_secs_f32(delta_time) else {
+            eprintln!("step needs a positive delta time, got {}", delta_time);
+            return;
+        };
+
+        if !self.prepare() {
+            return;
+        }
+
+        self.stepped_time += delta_time;
+        self.frame(self.stepped_time.as_secs_f64());
+    }
+
+    // Draws the world as seen by `camera` into `target`, outside of the frame
+    // loop: minimaps, golden images... Fails on headless applications.
+    pub fn render_to(&mut self, target: &Framebuffer, camera: &Camera) -> Result<(), String> {
+        let Some(backend) = &self.backend else {
+            return Err("headless applications cannot render".to_string());
+        };
+
+        let mut frame_context = FrameContext {
+            time: &self.time,
+            input: &self.input,
+            world: &mut self.world,
+            commands: &mut self.commands
+        };
+
+        target.bind();
+        render_view(&backend.shader, &mut self.schedule, &mut self.scenes, &mut frame_context, camera, target.width(), target.height());
+        target.unbind();
+
+        let (width, height) = backend.window.get_framebuffer_size();
+        unsafe { gl::Viewport(0, 0, width, height) }
+        Ok(())
+    }
+
+    // Saves the window content as a PNG file once the current or next frame
+    // has been drawn.
+    pub fn save_screenshot<P: AsRef<Path>>(&mut self, path: P) {
+        if self.backend.is_none() {
+            eprintln!("Headless applications have nothing to capture");
+            return;
+        }
+        self.screenshot_path = Some(path.as_ref().to_path_buf());
+    }
+
+    pub fn exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    pub fn should_exit(&self) -> bool {
+        self.exit_requested || self.backend.as_ref().is_some_and(|backend| backend.window.should_close())
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.backend.is_none()
+    }
 
-            self.camera.update(&frame_context);
+    pub fn time(&self) -> &Time {
+        &self.time
+    }
+
+    pub fn time_mut(&mut self) -> &mut Time {
+        &mut self.time
+    }
+
+    pub fn input_mut(&mut self) -> &mut InputHandler {
+        &mut self.input
+    }
+
+    fn prepare(&mut self) -> bool {
+        if !self.schedule.is_built()
+            && let Err(e) = self.schedule.build() {
+            eprintln!("Impossible de construire le planning des systèmes: {}", e);
+            return false;
+        }
+        true
+    }
+
+    fn frame(&mut self, now: f64) {
+        // Per-frame time logic
+        self.time.update(now);
+        let fixed_steps = self.time.consume_fixed_steps();
+
+        // Events and input
+        self.process_events();
+        self.process_input();
+
+        let mut scene_context = SceneContext {
+            time: &self.time,
+            input: &self.input,
+            world: &mut self.world,
+            commands: &mut self.commands,
+            camera: &mut self.camera
+        };
+
+        if self.scenes.update(&mut scene_context) {
+            self.exit();
+        }
 
-            for system in self.systems.iter_mut() {
-                system.update(&mut frame_context);
+        let mut frame_context = FrameContext {
+            time: &self.time,
+            input: &self.input,
+            world: &mut self.world,
+            commands: &mut self.commands
+        };
+
+        for stage in [Stage::PreUpdate, Stage::FixedUpdate, Stage::Update, Stage::PostUpdate] {
+            let runs = if stage == Stage::FixedUpdate { fixed_steps } else { 1 };
+            for _ in 0..runs {
+                self.schedule.run_stage(stage, &mut frame_context);
+                self.scenes.run_stage(stage, &mut frame_context);
             }
+        }
 
-            // Render
-            unsafe {
-                gl::ClearColor(0.2, 0.3, 0.3, 1.0);
-                gl::Clear(gl::COLOR_BUFFER_BIT);
+        // After the transform system, so the camera follows this frame's matrices.
+        self.camera.update(&frame_context);
 
-                self.shader.use_program();
+        // Render
+        if let Some(backend) = &self.backend {
+            let (width, height) = backend.window.get_framebuffer_size();
+            let (width, height) = (width as u32, height as u32);
 
-                let projection: Matrix4<f32> = ortho(0.0, self.width as f32, 0.0, self.height as f32, -1.0, 1.0);
-                self.shader.set_mat4(c_str!("projection"), &projection);
+            // Drawn offscreen first when screen effects are enabled
+            let mut post_process = frame_context.world.remove_resource::<PostProcessStack>();
+            let post_processing = match &mut post_process {
+                Some(stack) => stack.has_enabled_passes() && stack.begin(width, height),
+                None => false
+            };
 
-                let view = self.camera.get_view_matrix(self.width, self.height);
-                self.shader.set_mat4(c_str!("view"), &view);
+            render_view(&backend.shader, &mut self.schedule, &mut self.scenes, &mut frame_context, &self.camera, self.width, self.height);
 
-                for system in self.systems.iter_mut() {
-                    system.render(&mut frame_context, &self.shader);
+            if let Some(mut stack) = post_process {
+                if post_processing {
+                    stack.finish(width, height, self.time.unscaled_elapsed());
                 }
+                frame_context.world.insert_resource(stack);
             }
 
-            self.input.end_frame();
+            if let Some(path) = self.screenshot_path.take() {
+                let pixels = framebuffer::read_pixels(0, 0, width, height);
+                if let Err(e) = framebuffer::save_png(&path, width, height, &pixels) {
+                    eprintln!("Impossible d'enregistrer la capture d'écran {}: {}", path.display(), e);
+                }
+            }
+        }
 
-            // glfw: swap buffers and poll IO events
-            self.window.swap_buffers();
-            self.glfw.poll_events();
+        self.input.end_frame();
+        self.world.update_events();
+        self.world.update_removed_components();
+
+        // glfw: swap buffers and poll IO events
+        if let Some(backend) = &mut self.backend {
+            backend.window.swap_buffers();
+            backend.glfw.poll_events();
         }
     }
 
+    fn exit_scenes(&mut self) {
+        let mut scene_context = SceneContext {
+            time: &self.time,
+            input: &self.input,
+            world: &mut self.world,
+            commands: &mut self.commands,
+            camera: &mut self.camera
+        };
+        self.scenes.clear(&mut scene_context);
+    }
+
+    // Number of `Stage::FixedUpdate` runs per second (60 by default).
+    pub fn set_fixed_update_rate(&mut self, rate: f32) {
+        self.time.set_fixed_update_rate(rate);
+    }
+
+    // Fixed updates allowed in a single frame before the simulation falls behind (5 by default).
+    pub fn set_max_fixed_steps(&mut self, max_fixed_steps: u32) {
+        self.time.set_max_fixed_steps(max_fixed_steps);
+    }
+
     fn process_events(&mut self) {
         self.input.reset_scroll_delta();
 
-        for (_, event) in glfw::flush_messages(&self.events) {
+        let Some(backend) = &self.backend else {
+            return;
+        };
+
+        for (_, event) in glfw::flush_messages(&backend.events) {
             self.input.update(&event);
             match event {
                 glfw::WindowEvent::FramebufferSize(width, height) => {
@@ -182,7 +514,109 @@ impl Application {
 
     fn process_input(&mut self) {
         if self.input.is_key_pressed(Key::Escape) {
-            self.window.set_should_close(true)
+            self.exit();
+        }
+
+        if self.input.is_key_just_pressed(Key::F12) && self.backend.is_some() {
+            self.save_screenshot(format!("screenshot_{}.png", self.time.frame_index()));
         }
     }
 }
+
+// Clears the bound framebuffer and draws the render stage of the application
+// and of the top scene, seen by `camera` on a `width` x `height` target.
+fn render_view(shader: &Shader, schedule: &mut Schedule, scenes: &mut SceneStack, ctx: &mut FrameContext, camera: &Camera, width: u32, height: u32) {
+    unsafe {
+        gl::ClearColor(0.2, 0.3, 0.3, 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+
+        shader.use_program();
+
+        let projection: Matrix4<f32> = ortho(0.0, width as f32, 0.0, height as f32, -DEPTH_RANGE, DEPTH_RANGE);
+        shader.set_mat4(c_str!("projection"), &projection);
+
+        let view = camera.get_view_matrix(width, height);
+        shader.set_mat4(c_str!("view"), &view);
+    }
+
+    schedule.run_render(ctx, shader);
+    scenes.run_render(ctx, shader);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::system::System;
+
+    #[derive(Default)]
+    struct Counts {
+        updates: u32,
+        fixed_updates: u32
+    }
+
+    struct CountingSystem;
+
+    impl System for CountingSystem {
+        fn update(&mut self, ctx: &mut FrameContext) {
+            ctx.world.resource_mut::<Counts>().unwrap().updates += 1;
+        }
+
+        fn fixed_update(&mut self, ctx: &mut FrameContext) {
+            ctx.world.resource_mut::<Counts>().unwrap().fixed_updates += 1;
+        }
+    }
+
+    fn headless_app() -> Application {
+        let mut builder = ApplicationBuilder::new().headless().fixed_update_rate(10.0);
+        builder.add_plugin(DefaultPlugins);
+        builder.world_mut().insert_resource(Counts::default());
+        builder.add_system(Stage::Update, CountingSystem);
+        builder.add_system(Stage::FixedUpdate, CountingSystem);
+        builder.build()
+    }
+
+    fn counts(app: &Application) -> (u32, u32) {
+        let counts = app.world.resource::<Counts>().unwrap();
+        (counts.updates, counts.fixed_updates)
+    }
+
+    #[test]
+    fn headless_step_runs_one_frame() {
+        let mut app = headless_app();
+        assert!(app.is_headless());
+
+        app.step(0.25);
+        assert_eq!(counts(&app), (1, 2));
+        assert_eq!(app.time().frame_index(), 1);
+        assert!((app.time().delta_time() - 0.25).abs() < 1e-6);
+
+        app.step(0.06);
+        assert_eq!(counts(&app), (2, 3));
+        assert!((app.time().elapsed() - 0.31).abs() < 1e-6);
+        assert!(!app.should_exit());
+    }
+
+    #[test]
+    fn negative_step_is_ignored() {
+        let mut app = headless_app();
+        app.step(-1.0);
+        assert_eq!(counts(&app), (0, 0));
+        assert_eq!(app.time().frame_index(), 0);
+    }
+
+    #[test]
+    fn long_stepped_runs_keep_exact_deltas() {
+        let mut app = headless_app();
+        let delta_time = 1.0 / 60.0;
+        // A bit more than an hour of frames
+        for _ in 0..250_000 {
+            app.step(delta_time);
+        }
+
+        assert!((app.time().unscaled_delta_time() - delta_time).abs() < 1e-6);
+        assert!((app.time().unscaled_elapsed() - 250_000.0 / 60.0).abs() < 0.01);
+        // 10 fixed updates per second, give or take the last one
+        let (_, fixed_updates) = counts(&app);
+        assert!(fixed_updates.abs_diff(41_666) <= 1, "{} fixed updates", fixed_updates);
+    }
+}
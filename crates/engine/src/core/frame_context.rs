@@ -1,10 +1,25 @@
-use crate::{core::{input::InputHandler, time::Time}, graphics::managers::{AnimationManager, SpritesheetManager}, world::world::World};
+use crate::{core::{input::InputHandler, time::Time}, world::{commands::Commands, events::{EventReader, Events}, world::World}};
 
 #[allow(dead_code)]
 pub struct FrameContext<'a> {
     pub time: &'a Time,
     pub input: &'a InputHandler,
     pub world: &'a mut World,
-    pub spritesheet_manager: &'a mut SpritesheetManager,
-    pub animation_manager: &'a mut AnimationManager
+    pub commands: &'a mut Commands
+}
+
+impl FrameContext<'_> {
+    pub fn apply_commands(&mut self) {
+        self.commands.apply(self.world);
+    }
+
+    pub fn send_event<T: Send + Sync + 'static>(&mut self, event: T) {
+        self.world.send_event(event);
+    }
+
+    pub fn read_events<'b, T: Send + Sync + 'static>(&'b self, reader: &mut EventReader<T>) -> Vec<&'b T> {
+        self.world.resource::<Events<T>>()
+            .map(|events| reader.read(events).collect())
+            .unwrap_or_default()
+    }
 }
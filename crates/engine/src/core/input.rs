@@ -1,4 +1,4 @@
-use glfw::{Action, Key, WindowEvent};
+use glfw::{Action, Key, Modifiers, WindowEvent};
 use std::collections::HashMap;
 
 #[derive(Default)]
@@ -76,6 +76,15 @@ impl InputHandler {
         }
     }
 
+    // Simulated key presses, for headless applications and tests.
+    pub fn press_key(&mut self, key: Key) {
+        self.update(&WindowEvent::Key(key, 0, Action::Press, Modifiers::empty()));
+    }
+
+    pub fn release_key(&mut self, key: Key) {
+        self.update(&WindowEvent::Key(key, 0, Action::Release, Modifiers::empty()));
+    }
+
     pub fn end_frame(&mut self) {
         for state in self.key_states.values_mut() {
             state.just_pressed = false;
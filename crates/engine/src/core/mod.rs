@@ -1,6 +1,9 @@
 pub mod application;
 pub mod r#macro;
 pub mod time;
+pub mod timer;
 pub mod input;
 pub mod frame_context;
-pub mod path;
\ No newline at end of file
+pub mod path;
+pub mod scene;
+pub mod plugin;
\ No newline at end of file
@@ -0,0 +1,188 @@
+use serde::Deserialize;
+
+use crate::core::application::ApplicationBuilder;
+use crate::core::timer::{Stopwatch, Timer};
+use crate::graphics::animation::{AnimationComponent, AnimationComponentSerializer};
+use crate::graphics::layers::RenderLayers;
+use crate::graphics::managers::{AnimationManager, SpritesheetManager};
+use crate::graphics::postprocess::PostProcessStack;
+use crate::graphics::sprite::{SpriteRendererComponent, SpriteSerializer};
+use crate::world::components::{Children, Parent, TransformComponent, TransformInterpolation, TransformSerializer};
+use crate::world::prefab::ComponentRegistry;
+use crate::world::schedule::{IntoSystemConfig, Stage};
+use crate::world::snapshot::SnapshotRegistry;
+use crate::world::system::{AnimationSystem, SpriteRenderSystem, TimerSystem, TransformInterpolationSystem, TransformSystem};
+
+// A self-contained feature: the components, resources, systems and assets it
+// needs are registered in `build`, so an application only pays for the
+// plugins it adds.
+pub trait Plugin {
+    fn build(&self, app: &mut ApplicationBuilder);
+}
+
+// `Timer` and `Stopwatch` components, ticked before gameplay runs.
+pub struct TimePlugin;
+
+impl Plugin for TimePlugin {
+    fn build(&self, app: &mut ApplicationBuilder) {
+        app.world_mut().register_component::<Timer>();
+        app.world_mut().register_component::<Stopwatch>();
+        app.add_system(Stage::PreUpdate, TimerSystem.label("timers"));
+    }
+}
+
+// Transforms, the Parent/Children hierarchy and their interpolation.
+pub struct TransformPlugin;
+
+impl Plugin for TransformPlugin {
+    fn build(&self, app: &mut ApplicationBuilder) {
+        let world = app.world_mut();
+        world.register_component::<Parent>();
+        world.register_component::<Children>();
+        world.register_component::<TransformComponent>();
+        world.register_component::<TransformInterpolation>();
+
+        let registry = world.resource_mut::<ComponentRegistry>().unwrap();
+        registry.register_with("transform", |world, entity, value| {
+            let serializer = TransformSerializer::deserialize(value).map_err(|e| e.to_string())?;
+            world.add_component(entity, serializer.to_component());
+            Ok(())
+        });
+        registry.register_with("transform_interpolation", |world, entity, _| {
+            world.add_component(entity, TransformInterpolation::new());
+            Ok(())
+        });
+
+        let snapshots = world.resource_mut::<SnapshotRegistry>().unwrap();
+        snapshots.register_mapped::<Parent>("parent");
+        snapshots.register_mapped::<Children>("children");
+        snapshots.register::<TransformComponent>("transform");
+
+        app.add_system(Stage::FixedUpdate, TransformInterpolationSystem.label("transform_interpolation"));
+        app.add_system(Stage::PostUpdate, TransformSystem.label("transform"));
+    }
+}
+
+// Sprites, render layers and the render system. Needs a GL context.
+pub struct RenderPlugin;
+
+impl Plugin for RenderPlugin {
+    fn build(&self, app: &mut ApplicationBuilder) {
+        if app.is_headless() {
+            eprintln!("RenderPlugin needs a window, it is ignored by headless applications");
+            return;
+        }
+
+        let world = app.world_mut();
+        world.register_component::<SpriteRendererComponent>();
+        world.insert_resource(SpritesheetManager::new());
+        world.insert_resource(RenderLayers::new());
+
+        world.resource_mut::<ComponentRegistry>().unwrap().register_with("sprite", |world, entity, value| {
+            let serializer = SpriteSerializer::deserialize(value).map_err(|e| e.to_string())?;
+            let spritesheets = world.resource::<SpritesheetManager>().ok_or("no SpritesheetManager resource")?;
+            let layers = world.resource::<RenderLayers>().ok_or("no RenderLayers resource")?;
+            let sprite = serializer.to_component(spritesheets, layers)?;
+            world.add_component(entity, sprite);
+            Ok(())
+        });
+
+        app.add_system(Stage::Render, SpriteRenderSystem::new().label("sprite_render"));
+    }
+}
+
+// Frame animations of sprites. Adds the RenderPlugin it builds on.
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut ApplicationBuilder) {
+        app.add_plugin(RenderPlugin);
+        if app.world().resource::<SpritesheetManager>().is_none() {
+            eprintln!("AnimationPlugin needs a window, it is ignored by headless applications");
+            return;
+        }
+
+        let world = app.world_mut();
+        world.register_component::<AnimationComponent>();
+        world.insert_resource(AnimationManager::new());
+
+        world.resource_mut::<ComponentRegistry>().unwrap().register_with("animation", |world, entity, value| {
+            let serializer = AnimationComponentSerializer::deserialize(value).map_err(|e| e.to_string())?;
+            world.add_component(entity, serializer.to_component());
+            Ok(())
+        });
+        world.resource_mut::<SnapshotRegistry>().unwrap().register::<AnimationComponent>("animation");
+
+        app.add_system(Stage::PostUpdate, AnimationSystem.label("animation"));
+    }
+}
+
+// An empty `PostProcessStack`: passes are loaded by the game, once the GL
+// context exists.
+pub struct PostProcessPlugin;
+
+impl Plugin for PostProcessPlugin {
+    fn build(&self, app: &mut ApplicationBuilder) {
+        if app.is_headless() {
+            eprintln!("PostProcessPlugin needs a window, it is ignored by headless applications");
+            return;
+        }
+
+        app.world_mut().insert_resource(PostProcessStack::new());
+    }
+}
+
+// Every engine plugin the application can run with: time and transforms
+// everywhere, rendering, animation and post-processing only when there is a
+// window.
+pub struct DefaultPlugins;
+
+impl Plugin for DefaultPlugins {
+    fn build(&self, app: &mut ApplicationBuilder) {
+        app.add_plugin(TimePlugin);
+        app.add_plugin(TransformPlugin);
+
+        if !app.is_headless() {
+            app.add_plugin(RenderPlugin);
+            app.add_plugin(AnimationPlugin);
+            app.add_plugin(PostProcessPlugin);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::application::Application;
+
+    fn system_names(app: &mut Application, stage: Stage) -> Vec<String> {
+        app.schedule.build().unwrap();
+        app.schedule.resolved_order().into_iter()
+            .find(|(resolved_stage, _)| *resolved_stage == stage)
+            .map(|(_, names)| names)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn sub_plugins_go_through_the_duplicate_guard() {
+        let mut builder = ApplicationBuilder::new().headless();
+        builder.add_plugin(TimePlugin);
+        builder.add_plugin(DefaultPlugins);
+        builder.add_plugin(DefaultPlugins);
+        let mut app = builder.build();
+
+        assert_eq!(system_names(&mut app, Stage::PreUpdate), ["TimerSystem"]);
+        assert_eq!(system_names(&mut app, Stage::PostUpdate), ["TransformSystem"]);
+    }
+
+    #[test]
+    fn headless_skips_render_plugins() {
+        let mut builder = ApplicationBuilder::new().headless();
+        builder.add_plugin(AnimationPlugin);
+        let mut app = builder.build();
+
+        assert!(app.world.resource::<SpritesheetManager>().is_none());
+        assert!(app.world.resource::<AnimationManager>().is_none());
+        assert!(system_names(&mut app, Stage::Render).is_empty());
+    }
+}
@@ -0,0 +1,420 @@
+use crate::camera::Camera;
+use crate::core::frame_context::FrameContext;
+use crate::core::input::InputHandler;
+use crate::core::time::Time;
+use crate::glutils::shader::Shader;
+use crate::world::commands::Commands;
+use crate::world::schedule::{Schedule, Stage};
+use crate::world::world::World;
+
+// What scene hooks get to work with. Commands recorded here are applied as
+// soon as the hook returns.
+pub struct SceneContext<'a> {
+    pub time: &'a Time,
+    pub input: &'a InputHandler,
+    pub world: &'a mut World,
+    pub commands: &'a mut Commands,
+    pub camera: &'a mut Camera
+}
+
+pub enum SceneTransition {
+    None,
+    // Pauses the current scene and enters the new one on top of it.
+    Push(Box<dyn Scene>),
+    // Leaves the current scene and resumes the one below.
+    Pop,
+    // Leaves the current scene and enters the new one in its place.
+    Switch(Box<dyn Scene>),
+    Quit
+}
+
+// A game state such as a title screen, a loading screen or the game itself.
+// Only the scene on top of the stack is updated and runs its systems; the
+// application's own schedule keeps running whatever the scene.
+#[allow(unused_variables)]
+pub trait Scene {
+    fn name(&self) -> &str;
+
+    // Adds the systems that only run while this scene is on top. Called once,
+    // when the scene is entered.
+    fn build(&mut self, schedule: &mut Schedule) {}
+
+    fn on_enter(&mut self, ctx: &mut SceneContext) {}
+
+    fn on_exit(&mut self, ctx: &mut SceneContext) {}
+
+    // Another scene was pushed on top of this one.
+    fn on_pause(&mut self, ctx: &mut SceneContext) {}
+
+    // The scene above this one was popped.
+    fn on_resume(&mut self, ctx: &mut SceneContext) {}
+
+    fn update(&mut self, ctx: &mut SceneContext) -> SceneTransition {
+        SceneTransition::None
+    }
+}
+
+struct SceneEntry {
+    scene: Box<dyn Scene>,
+    schedule: Schedule
+}
+
+pub struct SceneStack {
+    scenes: Vec<SceneEntry>,
+    pending: Vec<SceneTransition>
+}
+
+impl Default for SceneStack {
+    fn default() -> Self {
+        SceneStack::new()
+    }
+}
+
+#[allow(dead_code)]
+impl SceneStack {
+    pub fn new() -> Self {
+        SceneStack {
+            scenes: Vec::new(),
+            pending: Vec::new()
+        }
+    }
+
+    // Transitions requested from outside a scene are applied at the start of the next frame.
+    pub fn push<S: Scene + 'static>(&mut self, scene: S) {
+        self.pending.push(SceneTransition::Push(Box::new(scene)));
+    }
+
+    pub fn pop(&mut self) {
+        self.pending.push(SceneTransition::Pop);
+    }
+
+    pub fn switch<S: Scene + 'static>(&mut self, scene: S) {
+        self.pending.push(SceneTransition::Switch(Box::new(scene)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    pub fn current_name(&self) -> Option<&str> {
+        self.scenes.last().map(|entry| entry.scene.name())
+    }
+
+    // Applies pending transitions, updates the top scene and applies the
+    // transition it asks for. Returns true when a scene asked to quit.
+    pub(crate) fn update(&mut self, ctx: &mut SceneContext) -> bool {
+        let mut quit = self.apply_transitions(ctx);
+
+        if let Some(entry) = self.scenes.last_mut() {
+            let transition = entry.scene.update(ctx);
+            ctx.commands.apply(ctx.world);
+            self.pending.push(transition);
+            quit |= self.apply_transitions(ctx);
+        }
+
+        quit
+    }
+
+    pub(crate) fn run_stage(&mut self, stage: Stage, ctx: &mut FrameContext) {
+        if let Some(entry) = self.scenes.last_mut() {
+            entry.schedule.run_stage(stage, ctx);
+        }
+    }
+
+    pub(crate) fn run_render(&mut self, ctx: &mut FrameContext, shader: &Shader) {
+        if let Some(entry) = self.scenes.last_mut() {
+            entry.schedule.run_render(ctx, shader);
+        }
+    }
+
+    // Exits every scene, top first.
+    pub(crate) fn clear(&mut self, ctx: &mut SceneContext) {
+        self.pending.clear();
+        while let Some(mut entry) = self.scenes.pop() {
+            entry.scene.on_exit(ctx);
+            ctx.commands.apply(ctx.world);
+        }
+    }
+
+    fn apply_transitions(&mut self, ctx: &mut SceneContext) -> bool {
+        let mut quit = false;
+
+        for transition in std::mem::take(&mut self.pending) {
+            match transition {
+                SceneTransition::None => {}
+                SceneTransition::Push(scene) => {
+                    if let Some(entry) = self.prepare(scene) {
+                        if let Some(top) = self.scenes.last_mut() {
+                            top.scene.on_pause(ctx);
+                            ctx.commands.apply(ctx.world);
+                        }
+                        self.enter(entry, ctx);
+                    }
+                }
+                SceneTransition::Pop => {
+                    match self.scenes.pop() {
+                        Some(mut entry) => {
+                            entry.scene.on_exit(ctx);
+                            ctx.commands.apply(ctx.world);
+
+                            if let Some(top) = self.scenes.last_mut() {
+                                top.scene.on_resume(ctx);
+                                ctx.commands.apply(ctx.world);
+                            }
+                        }
+                        None => eprintln!("Cannot pop a scene: the scene stack is empty")
+                    }
+                }
+                SceneTransition::Switch(scene) => {
+                    if let Some(entry) = self.prepare(scene) {
+                        if let Some(mut top) = self.scenes.pop() {
+                            top.scene.on_exit(ctx);
+                            ctx.commands.apply(ctx.world);
+                        }
+                        self.enter(entry, ctx);
+                    }
+                }
+                SceneTransition::Quit => quit = true
+            }
+        }
+
+        quit
+    }
+
+    // Builds the scene's systems up front so a broken schedule leaves the stack untouched.
+    fn prepare(&mut self, mut scene: Box<dyn Scene>) -> Option<SceneEntry> {
+        let mut schedule = Schedule::new();
+        scene.build(&mut schedule);
+
+        if let Err(e) = schedule.build() {
+            eprintln!("Impossible de construire le planning de la scène '{}': {}", scene.name(), e);
+            return None;
+        }
+
+        Some(SceneEntry { scene, schedule })
+    }
+
+    fn enter(&mut self, mut entry: SceneEntry, ctx: &mut SceneContext) {
+        entry.scene.on_enter(ctx);
+        ctx.commands.apply(ctx.world);
+        self.scenes.push(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::schedule::IntoSystemConfig;
+    use crate::world::system::System;
+
+    struct Log(Vec<String>);
+
+    // Records its hooks in the `Log` resource, and adds a system doing the same.
+    struct Recorder {
+        name: &'static str,
+        next: Option<SceneTransition>
+    }
+
+    impl Recorder {
+        fn new(name: &'static str) -> Self {
+            Recorder { name, next: None }
+        }
+
+        fn log(&self, ctx: &mut SceneContext, hook: &str) {
+            ctx.world.resource_mut::<Log>().unwrap().0.push(format!("{}:{}", self.name, hook));
+        }
+    }
+
+    struct RecorderSystem(&'static str);
+
+    impl System for RecorderSystem {
+        fn update(&mut self, ctx: &mut FrameContext) {
+            ctx.world.resource_mut::<Log>().unwrap().0.push(format!("{}:system", self.0));
+        }
+    }
+
+    impl Scene for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn build(&mut self, schedule: &mut Schedule) {
+            schedule.add_system(Stage::Update, RecorderSystem(self.name).label(self.name));
+        }
+
+        fn on_enter(&mut self, ctx: &mut SceneContext) {
+            self.log(ctx, "enter");
+        }
+
+        fn on_exit(&mut self, ctx: &mut SceneContext) {
+            self.log(ctx, "exit");
+        }
+
+        fn on_pause(&mut self, ctx: &mut SceneContext) {
+            self.log(ctx, "pause");
+        }
+
+        fn on_resume(&mut self, ctx: &mut SceneContext) {
+            self.log(ctx, "resume");
+        }
+
+        fn update(&mut self, ctx: &mut SceneContext) -> SceneTransition {
+            self.log(ctx, "update");
+            self.next.take().unwrap_or(SceneTransition::None)
+        }
+    }
+
+    struct Harness {
+        time: Time,
+        input: InputHandler,
+        world: World,
+        commands: Commands,
+        camera: Camera
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let mut world = World::new();
+            world.insert_resource(Log(Vec::new()));
+            let commands = Commands::new(&world);
+
+            Harness { time: Time::new(), input: InputHandler::new(), world, commands, camera: Camera::default() }
+        }
+
+        fn update(&mut self, scenes: &mut SceneStack) -> bool {
+            let mut ctx = SceneContext {
+                time: &self.time,
+                input: &self.input,
+                world: &mut self.world,
+                commands: &mut self.commands,
+                camera: &mut self.camera
+            };
+            scenes.update(&mut ctx)
+        }
+
+        fn run_systems(&mut self, scenes: &mut SceneStack) {
+            let mut ctx = FrameContext { time: &self.time, input: &self.input, world: &mut self.world, commands: &mut self.commands };
+            scenes.run_stage(Stage::Update, &mut ctx);
+        }
+
+        fn take_log(&mut self) -> Vec<String> {
+            std::mem::take(&mut self.world.resource_mut::<Log>().unwrap().0)
+        }
+    }
+
+    #[test]
+    fn push_pauses_and_pop_resumes() {
+        let mut harness = Harness::new();
+        let mut scenes = SceneStack::new();
+
+        scenes.push(Recorder::new("title"));
+        harness.update(&mut scenes);
+        assert_eq!(harness.take_log(), ["title:enter", "title:update"]);
+
+        scenes.push(Recorder::new("menu"));
+        harness.update(&mut scenes);
+        assert_eq!(harness.take_log(), ["title:pause", "menu:enter", "menu:update"]);
+        assert_eq!(scenes.len(), 2);
+        assert_eq!(scenes.current_name(), Some("menu"));
+
+        scenes.pop();
+        harness.update(&mut scenes);
+        assert_eq!(harness.take_log(), ["menu:exit", "title:resume", "title:update"]);
+        assert_eq!(scenes.current_name(), Some("title"));
+    }
+
+    #[test]
+    fn switch_exits_before_entering() {
+        let mut harness = Harness::new();
+        let mut scenes = SceneStack::new();
+        scenes.push(Recorder::new("base"));
+        scenes.push(Recorder::new("title"));
+        harness.update(&mut scenes);
+        harness.take_log();
+
+        scenes.switch(Recorder::new("game"));
+        harness.update(&mut scenes);
+        assert_eq!(harness.take_log(), ["title:exit", "game:enter", "game:update"]);
+        assert_eq!(scenes.len(), 2);
+
+        // Popping the switched scene resumes the one below
+        scenes.pop();
+        harness.update(&mut scenes);
+        assert_eq!(harness.take_log(), ["game:exit", "base:resume", "base:update"]);
+    }
+
+    #[test]
+    fn transition_returned_by_update_applies_in_the_same_frame() {
+        let mut harness = Harness::new();
+        let mut scenes = SceneStack::new();
+        let mut title = Recorder::new("title");
+        title.next = Some(SceneTransition::Switch(Box::new(Recorder::new("game"))));
+        scenes.push(title);
+
+        assert!(!harness.update(&mut scenes));
+        assert_eq!(harness.take_log(), ["title:enter", "title:update", "title:exit", "game:enter"]);
+
+        let mut quitting = Recorder::new("quitting");
+        quitting.next = Some(SceneTransition::Quit);
+        scenes.switch(quitting);
+        assert!(harness.update(&mut scenes));
+    }
+
+    #[test]
+    fn only_the_top_scene_runs_its_systems() {
+        let mut harness = Harness::new();
+        let mut scenes = SceneStack::new();
+        scenes.push(Recorder::new("game"));
+        harness.update(&mut scenes);
+        harness.take_log();
+
+        harness.run_systems(&mut scenes);
+        assert_eq!(harness.take_log(), ["game:system"]);
+
+        scenes.push(Recorder::new("pause"));
+        harness.update(&mut scenes);
+        harness.take_log();
+        harness.run_systems(&mut scenes);
+        assert_eq!(harness.take_log(), ["pause:system"]);
+
+        scenes.pop();
+        harness.update(&mut scenes);
+        harness.take_log();
+        harness.run_systems(&mut scenes);
+        assert_eq!(harness.take_log(), ["game:system"]);
+    }
+
+    #[test]
+    fn clear_exits_top_first() {
+        let mut harness = Harness::new();
+        let mut scenes = SceneStack::new();
+        scenes.push(Recorder::new("bottom"));
+        scenes.push(Recorder::new("top"));
+        harness.update(&mut scenes);
+        harness.take_log();
+
+        let mut ctx = SceneContext {
+            time: &harness.time,
+            input: &harness.input,
+            world: &mut harness.world,
+            commands: &mut harness.commands,
+            camera: &mut harness.camera
+        };
+        scenes.clear(&mut ctx);
+        assert_eq!(harness.take_log(), ["top:exit", "bottom:exit"]);
+        assert!(scenes.is_empty());
+    }
+
+    #[test]
+    fn pop_on_empty_stack_is_ignored() {
+        let mut harness = Harness::new();
+        let mut scenes = SceneStack::new();
+        scenes.pop();
+        assert!(!harness.update(&mut scenes));
+        assert!(scenes.is_empty());
+    }
+}
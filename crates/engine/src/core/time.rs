@@ -1,9 +1,28 @@
+const DEFAULT_FIXED_UPDATE_RATE: f32 = 60.0;
+const DEFAULT_MAX_FIXED_STEPS: u32 = 5;
+
 pub struct Time {
+    // `delta_time` is the frame time scaled by `time_scale` (zero while
+    // paused); `unscaled_delta_time` is the real one, for UI and tools.
     delta_time: f32,
-    last_frame: f32,
+    unscaled_delta_time: f32,
+    time_scale: f32,
+    paused: bool,
+    elapsed: f32,
+    frame_index: u64,
+    // Clock readings are kept in f64: in f32, deltas computed after a few
+    // hours of run time would be off by more than a millisecond.
+    last_frame: f64,
     fps: f32,
     frame_count: u32,
-    last_fps_update: f32
+    last_fps_update: f64,
+
+    // Fixed-timestep simulation: frame time is accumulated and spent in
+    // `fixed_delta_time` steps, at most `max_fixed_steps` per frame.
+    fixed_delta_time: f32,
+    max_fixed_steps: u32,
+    accumulator: f32,
+    interpolation_alpha: f32
 }
 
 #[allow(dead_code)]
@@ -11,23 +30,35 @@ impl Time {
     pub fn new() -> Self {
         Self {
             delta_time: 0.0,
+            unscaled_delta_time: 0.0,
+            time_scale: 1.0,
+            paused: false,
+            elapsed: 0.0,
+            frame_index: 0,
             last_frame: 0.0,
             fps: 0.0,
             frame_count: 0,
-            last_fps_update: 0.0
+            last_fps_update: 0.0,
+            fixed_delta_time: 1.0 / DEFAULT_FIXED_UPDATE_RATE,
+            max_fixed_steps: DEFAULT_MAX_FIXED_STEPS,
+            accumulator: 0.0,
+            interpolation_alpha: 0.0
         }
     }
 
     pub fn update(&mut self, current_time: f64) {
-        let current_frame = current_time as f32;
-        self.delta_time = current_frame - self.last_frame;
-        self.last_frame = current_frame;
+        self.unscaled_delta_time = (current_time - self.last_frame) as f32;
+        self.last_frame = current_time;
+
+        self.delta_time = if self.paused { 0.0 } else { self.unscaled_delta_time * self.time_scale };
+        self.elapsed += self.delta_time;
+        self.frame_index += 1;
 
         self.frame_count += 1;
-        if current_frame - self.last_fps_update >= 1.0 {
-            self.fps = self.frame_count as f32 / (current_frame - self.last_fps_update);
+        if current_time - self.last_fps_update >= 1.0 {
+            self.fps = (self.frame_count as f64 / (current_time - self.last_fps_update)) as f32;
             self.frame_count = 0;
-            self.last_fps_update = current_frame;
+            self.last_fps_update = current_time;
         }
     }
 
@@ -35,7 +66,172 @@ impl Time {
         self.delta_time
     }
 
+    pub fn unscaled_delta_time(&self) -> f32 {
+        self.unscaled_delta_time
+    }
+
+    // Game time since the start: the sum of the scaled delta times.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    // Real time of the current frame, in seconds since the clock started.
+    pub fn unscaled_elapsed(&self) -> f32 {
+        self.last_frame as f32
+    }
+
+    // Number of frames since the start, the current one included.
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    // 1 is real time, 0.5 slow motion. Fixed updates keep their step and run
+    // less often.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        if time_scale < 0.0 {
+            eprintln!("Time scale cannot be negative, got {}", time_scale);
+            return;
+        }
+        self.time_scale = time_scale;
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    // Stops gameplay time: scaled delta and fixed updates. The unscaled values
+    // keep going for UI.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
     pub fn fps(&self) -> f32 {
         self.fps
     }
-}
\ No newline at end of file
+
+    // Number of fixed updates per second.
+    pub fn set_fixed_update_rate(&mut self, rate: f32) {
+        if rate <= 0.0 {
+            eprintln!("Fixed update rate must be positive, got {}", rate);
+            return;
+        }
+        self.fixed_delta_time = 1.0 / rate;
+    }
+
+    // Upper bound on fixed updates run in one frame. When a frame takes longer
+    // than that, the remaining time is dropped and the simulation slows down
+    // instead of spiralling.
+    pub fn set_max_fixed_steps(&mut self, max_fixed_steps: u32) {
+        self.max_fixed_steps = max_fixed_steps.max(1);
+    }
+
+    pub fn fixed_delta_time(&self) -> f32 {
+        self.fixed_delta_time
+    }
+
+    // How far the current frame is between the last fixed update and the next
+    // one, in [0, 1). Used to interpolate what is rendered.
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.interpolation_alpha
+    }
+
+    // Adds the frame time to the accumulator and returns how many fixed
+    // updates should run this frame.
+    pub(crate) fn consume_fixed_steps(&mut self) -> u32 {
+        self.accumulator += self.delta_time;
+
+        let mut steps = (self.accumulator / self.fixed_delta_time) as u32;
+        if steps > self.max_fixed_steps {
+            steps = self.max_fixed_steps;
+            self.accumulator %= self.fixed_delta_time;
+        } else {
+            self.accumulator -= steps as f32 * self.fixed_delta_time;
+        }
+
+        self.interpolation_alpha = (self.accumulator / self.fixed_delta_time).clamp(0.0, 1.0);
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{} != {}", actual, expected);
+    }
+
+    fn time_at(rate: f32) -> Time {
+        let mut time = Time::new();
+        time.set_fixed_update_rate(rate);
+        time
+    }
+
+    #[test]
+    fn accumulator_runs_whole_steps_and_keeps_the_rest() {
+        let mut time = time_at(10.0);
+
+        time.update(0.25);
+        assert_eq!(time.consume_fixed_steps(), 2);
+        assert_close(time.interpolation_alpha(), 0.5);
+
+        // The leftover half step completes with the next frame
+        time.update(0.32);
+        assert_eq!(time.consume_fixed_steps(), 1);
+        assert_close(time.interpolation_alpha(), 0.2);
+
+        time.update(0.33);
+        assert_eq!(time.consume_fixed_steps(), 0);
+        assert_close(time.interpolation_alpha(), 0.3);
+    }
+
+    #[test]
+    fn steps_add_up_over_many_frames() {
+        let mut time = time_at(60.0);
+        let mut steps = 0;
+        for frame in 1..=144 {
+            time.update(frame as f64 / 144.0);
+            steps += time.consume_fixed_steps();
+            assert!((0.0..1.0).contains(&time.interpolation_alpha()));
+        }
+
+        // One second of frames at 144 Hz, with a tolerance for rounding
+        assert!((59..=60).contains(&steps), "{} steps", steps);
+    }
+
+    #[test]
+    fn long_frame_is_capped() {
+        let mut time = time_at(10.0);
+        time.set_max_fixed_steps(3);
+
+        time.update(1.05);
+        assert_eq!(time.consume_fixed_steps(), 3);
+        // The dropped time does not carry over to the next frames
+        assert_close(time.interpolation_alpha(), 0.5);
+        time.update(1.06);
+        assert_eq!(time.consume_fixed_steps(), 0);
+    }
+
+    #[test]
+    fn time_scale_and_pause_affect_fixed_steps() {
+        let mut time = time_at(10.0);
+        time.set_time_scale(0.5);
+        time.update(0.4);
+        assert_eq!(time.consume_fixed_steps(), 2);
+        assert_close(time.unscaled_delta_time(), 0.4);
+
+        time.pause();
+        time.update(1.4);
+        assert_eq!(time.consume_fixed_steps(), 0);
+        assert_eq!(time.delta_time(), 0.0);
+        assert_close(time.elapsed(), 0.2);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::world::components::Component;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerMode {
+    // Finishes once and stays finished until reset.
+    Once,
+    // Starts over each time it finishes, keeping the overshoot.
+    Repeating
+}
+
+// Counts down a duration. As a component it is ticked by the `TimerSystem`
+// with the scaled delta time, so it stops while the game is paused; it can
+// also be owned by a system and ticked by hand.
+#[derive(Clone, Debug)]
+pub struct Timer {
+    duration: f32,
+    elapsed: f32,
+    mode: TimerMode,
+    paused: bool,
+    finished: bool,
+    // How many times the timer finished during the last tick. More than one
+    // when a repeating timer is shorter than the tick.
+    times_finished: u32
+}
+
+impl Component for Timer {}
+
+#[allow(dead_code)]
+impl Timer {
+    pub fn new(duration: f32, mode: TimerMode) -> Self {
+        Timer {
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+            mode,
+            paused: false,
+            finished: false,
+            times_finished: 0
+        }
+    }
+
+    pub fn tick(&mut self, delta_time: f32) -> &Self {
+        self.times_finished = 0;
+
+        if self.paused || (self.finished && self.mode == TimerMode::Once) {
+            return self;
+        }
+
+        self.elapsed += delta_time;
+        if self.elapsed < self.duration {
+            self.finished = false;
+            return self;
+        }
+
+        self.finished = true;
+        match self.mode {
+            TimerMode::Once => {
+                self.elapsed = self.duration;
+                self.times_finished = 1;
+            }
+            TimerMode::Repeating if self.duration == 0.0 => {
+                self.elapsed = 0.0;
+                self.times_finished = 1;
+            }
+            TimerMode::Repeating => {
+                self.times_finished = (self.elapsed / self.duration) as u32;
+                self.elapsed %= self.duration;
+            }
+        }
+
+        self
+    }
+
+    // True for the tick during which the timer ran out.
+    pub fn just_finished(&self) -> bool {
+        self.times_finished > 0
+    }
+
+    pub fn times_finished(&self) -> u32 {
+        self.times_finished
+    }
+
+    // A `Once` timer stays finished; a repeating one only on the ticks it wraps.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.finished = false;
+        self.times_finished = 0;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn set_duration(&mut self, duration: f32) {
+        self.duration = duration.max(0.0);
+    }
+
+    pub fn mode(&self) -> TimerMode {
+        self.mode
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn remaining(&self) -> f32 {
+        (self.duration - self.elapsed).max(0.0)
+    }
+
+    // Progress from 0 (just started) to 1 (finished).
+    pub fn fraction(&self) -> f32 {
+        if self.duration == 0.0 {
+            return 1.0;
+        }
+        self.elapsed / self.duration
+    }
+}
+
+// Counts time up, with no end. Ticked like `Timer`.
+#[derive(Clone, Debug)]
+pub struct Stopwatch {
+    elapsed: f32,
+    paused: bool
+}
+
+impl Component for Stopwatch {}
+
+impl Default for Stopwatch {
+    fn default() -> Self {
+        Stopwatch::new()
+    }
+}
+
+#[allow(dead_code)]
+impl Stopwatch {
+    pub fn new() -> Self {
+        Stopwatch {
+            elapsed: 0.0,
+            paused: false
+        }
+    }
+
+    pub fn tick(&mut self, delta_time: f32) -> &Self {
+        if !self.paused {
+            self.elapsed += delta_time;
+        }
+        self
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeating_timer_counts_every_completion_of_a_tick() {
+        let mut timer = Timer::new(0.25, TimerMode::Repeating);
+
+        timer.tick(0.875);
+        assert!(timer.just_finished());
+        assert_eq!(timer.times_finished(), 3);
+        // The overshoot is kept for the next round
+        assert_eq!(timer.elapsed(), 0.125);
+
+        timer.tick(0.0625);
+        assert!(!timer.just_finished());
+        assert!(!timer.finished());
+        assert_eq!(timer.fraction(), 0.75);
+
+        timer.tick(0.0625);
+        assert_eq!(timer.times_finished(), 1);
+        assert_eq!(timer.elapsed(), 0.0);
+    }
+
+    #[test]
+    fn once_timer_finishes_once() {
+        let mut timer = Timer::new(0.5, TimerMode::Once);
+
+        timer.tick(2.0);
+        assert_eq!(timer.times_finished(), 1);
+        assert_eq!(timer.remaining(), 0.0);
+        assert_eq!(timer.fraction(), 1.0);
+
+        timer.tick(1.0);
+        assert!(!timer.just_finished());
+        assert!(timer.finished());
+    }
+
+    #[test]
+    fn paused_timer_does_not_advance() {
+        let mut timer = Timer::new(1.0, TimerMode::Once);
+        timer.tick(0.25);
+
+        timer.pause();
+        timer.tick(5.0);
+        assert!(timer.is_paused());
+        assert_eq!(timer.elapsed(), 0.25);
+        assert!(!timer.finished());
+
+        timer.resume();
+        timer.tick(0.75);
+        assert!(timer.just_finished());
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut timer = Timer::new(0.5, TimerMode::Once);
+        timer.tick(1.0);
+        assert!(timer.finished());
+
+        timer.reset();
+        assert!(!timer.finished());
+        assert!(!timer.just_finished());
+        assert_eq!(timer.elapsed(), 0.0);
+
+        timer.tick(0.25);
+        assert_eq!(timer.remaining(), 0.25);
+        timer.tick(0.25);
+        assert!(timer.just_finished());
+    }
+
+    #[test]
+    fn zero_duration_finishes_every_tick() {
+        let mut timer = Timer::new(0.0, TimerMode::Repeating);
+        for _ in 0..3 {
+            timer.tick(0.1);
+            assert_eq!(timer.times_finished(), 1);
+        }
+    }
+
+    #[test]
+    fn stopwatch_pause_resume_and_reset() {
+        let mut stopwatch = Stopwatch::new();
+        stopwatch.tick(0.5);
+
+        stopwatch.pause();
+        stopwatch.tick(3.0);
+        assert_eq!(stopwatch.elapsed(), 0.5);
+
+        stopwatch.resume();
+        stopwatch.tick(0.25);
+        assert_eq!(stopwatch.elapsed(), 0.75);
+
+        stopwatch.reset();
+        assert_eq!(stopwatch.elapsed(), 0.0);
+        assert!(!stopwatch.is_paused());
+    }
+}
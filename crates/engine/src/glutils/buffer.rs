@@ -72,6 +72,19 @@ impl VertexBuffer {
             );
         }
     }
+
+    // For data rewritten every frame: the previous storage is orphaned
+    // instead of waiting for the draws still reading it.
+    pub fn set_stream_data<T>(&self, data: &[T]) {
+        unsafe {
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+                gl::STREAM_DRAW
+            );
+        }
+    }
 }
 
 impl Drop for VertexBuffer {
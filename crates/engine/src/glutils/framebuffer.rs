@@ -0,0 +1,237 @@
+use std::io;
+use std::path::Path;
+
+use gl::types::*;
+use image::ColorType;
+
+use crate::glutils::texture::Texture;
+
+// Offscreen render target with an RGBA color texture. Bind it, draw, then
+// read the pixels back or sample `texture` in a later pass.
+pub struct Framebuffer {
+    id: GLuint,
+    texture: Texture
+}
+
+#[allow(dead_code)]
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Result<Self, String> {
+        let texture = Texture::empty(width, height);
+
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, texture.id, 0);
+
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+            // Built before the check so that Drop cleans up on failure too.
+            let framebuffer = Framebuffer { id, texture };
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("framebuffer is incomplete (status 0x{:X})", status));
+            }
+            Ok(framebuffer)
+        }
+    }
+
+    // Makes it the target of the following draws, over its whole size.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.texture.width as GLsizei, self.texture.height as GLsizei);
+        }
+    }
+
+    // Back to the window. The caller restores the window viewport.
+    pub fn unbind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0); }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height
+    }
+
+    // RGBA8 pixels, top row first.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.id); }
+        let pixels = read_pixels(0, 0, self.width(), self.height());
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0); }
+        pixels
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        save_png(path, self.width(), self.height(), &self.read_pixels())
+    }
+
+    // Compares the content with a golden PNG, see `compare_png`.
+    pub fn compare_png<P: AsRef<Path>>(&self, path: P, tolerance: u8) -> Result<(), String> {
+        compare_png(path, self.width(), self.height(), &self.read_pixels(), tolerance)
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            gl::DeleteTextures(1, &self.texture.id);
+        }
+    }
+}
+
+// Reads RGBA8 pixels from the bound framebuffer. OpenGL returns the bottom
+// row first; rows are flipped so that the result is top row first, like
+// image files.
+pub fn read_pixels(x: i32, y: i32, width: u32, height: u32) -> Vec<u8> {
+    let row_size = width as usize * 4;
+    let mut pixels = vec![0u8; row_size * height as usize];
+
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(x, y, width as GLsizei, height as GLsizei, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+    }
+
+    let mut flipped = Vec::with_capacity(pixels.len());
+    for row in pixels.chunks_exact(row_size).rev() {
+        flipped.extend_from_slice(row);
+    }
+    flipped
+}
+
+// Saves RGBA8 pixels, top row first, as a PNG file.
+pub fn save_png<P: AsRef<Path>>(path: P, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    if path.extension().is_none_or(|extension| extension != "png") {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a .png path", path.display())));
+    }
+
+    image::save_buffer(path, pixels, width, height, ColorType::RGBA(8))
+}
+
+// Loads a PNG file as RGBA8 pixels, top row first: (width, height, pixels).
+pub fn load_png<P: AsRef<Path>>(path: P) -> Result<(u32, u32, Vec<u8>), String> {
+    let path = path.as_ref();
+    let image = image::open(path).map_err(|e| format!("cannot load {}: {}", path.display(), e))?;
+    let image = image.to_rgba();
+    let (width, height) = image.dimensions();
+    Ok((width, height, image.into_raw()))
+}
+
+// Compares two RGBA8 images of `width` x `height` pixels. A channel may differ
+// by up to `tolerance`, to absorb rounding differences between GL drivers. The
+// error tells how many pixels differ and where the first one is.
+pub fn compare_pixels(width: u32, height: u32, expected: &[u8], actual: &[u8], tolerance: u8) -> Result<(), String> {
+    let size = width as usize * height as usize * 4;
+    if expected.len() != size || actual.len() != size {
+        return Err(format!("expected {} bytes for {}x{} pixels, got {} and {}", size, width, height, expected.len(), actual.len()));
+    }
+
+    let mut differing = 0;
+    let mut first = None;
+    for (index, (expected, actual)) in expected.chunks_exact(4).zip(actual.chunks_exact(4)).enumerate() {
+        let matches = expected.iter().zip(actual).all(|(a, b)| a.abs_diff(*b) <= tolerance);
+        if !matches {
+            differing += 1;
+            first.get_or_insert((index, expected, actual));
+        }
+    }
+
+    match first {
+        None => Ok(()),
+        Some((index, expected, actual)) => Err(format!(
+            "{} pixels differ, the first at ({}, {}): expected {:?}, got {:?}",
+            differing, index % width as usize, index / width as usize, expected, actual
+        ))
+    }
+}
+
+// Compares RGBA8 pixels, top row first, with a golden PNG file.
+pub fn compare_png<P: AsRef<Path>>(path: P, width: u32, height: u32, pixels: &[u8], tolerance: u8) -> Result<(), String> {
+    let path = path.as_ref();
+    let (golden_width, golden_height, golden) = load_png(path)?;
+    if (golden_width, golden_height) != (width, height) {
+        return Err(format!("{} is {}x{}, the image is {}x{}", path.display(), golden_width, golden_height, width, height));
+    }
+
+    compare_pixels(width, height, &golden, pixels, tolerance)
+        .map_err(|e| format!("{} does not match: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2x2: red, green / blue, white
+    fn pixels() -> Vec<u8> {
+        vec![
+            255, 0, 0, 255,    0, 255, 0, 255,
+            0, 0, 255, 255,    255, 255, 255, 255
+        ]
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("engine_{}_{}.png", name, std::process::id()))
+    }
+
+    #[test]
+    fn identical_pixels_match() {
+        assert_eq!(compare_pixels(2, 2, &pixels(), &pixels(), 0), Ok(()));
+    }
+
+    #[test]
+    fn tolerance_absorbs_small_differences() {
+        let mut actual = pixels();
+        actual[4] = 3;
+        actual[15] = 250;
+
+        assert!(compare_pixels(2, 2, &pixels(), &actual, 5).is_ok());
+        let error = compare_pixels(2, 2, &pixels(), &actual, 4).unwrap_err();
+        assert!(error.starts_with("1 pixels differ, the first at (1, 1)"), "{}", error);
+    }
+
+    #[test]
+    fn differing_pixels_are_counted() {
+        let mut actual = pixels();
+        actual[8..12].copy_from_slice(&[0, 0, 0, 255]);
+        actual[12..16].copy_from_slice(&[0, 0, 0, 255]);
+
+        let error = compare_pixels(2, 2, &pixels(), &actual, 0).unwrap_err();
+        assert!(error.starts_with("2 pixels differ, the first at (0, 1)"), "{}", error);
+    }
+
+    #[test]
+    fn sizes_must_match() {
+        assert!(compare_pixels(2, 2, &pixels(), &pixels()[..12], 0).is_err());
+        assert!(compare_pixels(1, 2, &pixels(), &pixels(), 0).is_err());
+    }
+
+    #[test]
+    fn golden_png_round_trip() {
+        let path = temp_path("golden");
+        save_png(&path, 2, 2, &pixels()).unwrap();
+
+        assert_eq!(load_png(&path), Ok((2, 2, pixels())));
+        assert!(compare_png(&path, 2, 2, &pixels(), 0).is_ok());
+
+        let mut actual = pixels();
+        actual[0] = 0;
+        assert!(compare_png(&path, 2, 2, &actual, 0).is_err());
+        assert!(compare_png(&path, 1, 4, &pixels(), 0).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_golden_is_an_error() {
+        assert!(compare_png(temp_path("missing"), 2, 2, &pixels(), 0).is_err());
+    }
+}
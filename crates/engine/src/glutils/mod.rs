@@ -1,3 +1,4 @@
 pub mod buffer;
 pub mod shader;
-pub mod texture;
\ No newline at end of file
+pub mod texture;
+pub mod framebuffer;
\ No newline at end of file
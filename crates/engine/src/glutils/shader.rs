@@ -7,7 +7,7 @@ use std::str;
 use gl;
 use gl::types::*;
 
-use cgmath::{Matrix, Matrix4, Vector3};
+use cgmath::{Matrix, Matrix4, Vector2, Vector3, Vector4};
 use cgmath::prelude::*;
 
 use crate::core::path::get_path_to_asset;
@@ -88,10 +88,22 @@ impl Shader {
         unsafe { gl::Uniform1f(gl::GetUniformLocation(self.id, name.as_ptr()), value); }
     }
 
+    /// # Safety
+    /// Needs a current GL context, with this program in use.
+    pub unsafe fn set_vec2(&self, name: &CStr, value: &Vector2<f32>) {
+        unsafe { gl::Uniform2fv(gl::GetUniformLocation(self.id, name.as_ptr()), 1, value.as_ptr()); }
+    }
+
     pub unsafe fn set_vec3(&self, name: &CStr, value: &Vector3<f32>) {
         unsafe { gl::Uniform3fv(gl::GetUniformLocation(self.id, name.as_ptr()), 1, value.as_ptr()); }
     }
 
+    /// # Safety
+    /// Needs a current GL context, with this program in use.
+    pub unsafe fn set_vec4(&self, name: &CStr, value: &Vector4<f32>) {
+        unsafe { gl::Uniform4fv(gl::GetUniformLocation(self.id, name.as_ptr()), 1, value.as_ptr()); }
+    }
+
     pub unsafe fn set_mat4(&self, name: &CStr, mat: &Matrix4<f32>) {
         unsafe { gl::UniformMatrix4fv(gl::GetUniformLocation(self.id, name.as_ptr()), 1, gl::FALSE, mat.as_ptr()); }
     }
@@ -77,6 +77,43 @@ impl Texture {
         texture
     }
 
+    /// Crée une texture RGBA vide, par exemple comme cible d'un framebuffer.
+    pub fn empty(width: u32, height: u32) -> Self {
+        let mut texture = Texture {
+            id: 0,
+            path: String::new(),
+            width,
+            height,
+        };
+
+        unsafe {
+            gl::GenTextures(1, &mut texture.id);
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+
+            // Pas de données : la mémoire est seulement réservée
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+
+            // Pas de mipmaps, et pas de répétition sur les bords
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        texture
+    }
+
     /// Active une unité de texture spécifique (ex: TEXTURE0, TEXTURE1, ...).
     pub fn active(&self, unit: u32) {
         unsafe {
@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use crate::world::components::Component;
 
 #[derive(Deserialize, Debug)]
@@ -22,7 +22,14 @@ pub struct Animation {
     pub frames: Vec<String>,
 }
 
-#[derive(Clone)]
+// Prefab description of an animation component, optionally already playing.
+#[derive(Deserialize, Debug)]
+pub struct AnimationComponentSerializer {
+    #[serde(default)]
+    pub play: Option<String>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AnimationComponent {
     pub current_animation: Option<String>,
     pub current_frame_index: usize,
@@ -57,4 +64,14 @@ impl AnimationComponent {
         self.current_frame_index = 0;
         self.timer = 0.0;
     }
+}
+
+impl AnimationComponentSerializer {
+    pub fn to_component(&self) -> AnimationComponent {
+        let mut component = AnimationComponent::new();
+        if let Some(animation_name) = &self.play {
+            component.play(animation_name);
+        }
+        component
+    }
 }
\ No newline at end of file
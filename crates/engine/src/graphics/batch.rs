@@ -0,0 +1,323 @@
+use std::cmp::Ordering;
+
+use cgmath::{Matrix4, Vector4};
+
+use crate::graphics::layers::RenderLayer;
+
+// Corners of the unit quad, in the order of `Sprite::tex_coords`: two
+// triangles, top-left / bottom-left / bottom-right and top-left /
+// bottom-right / top-right.
+pub const QUAD_POSITIONS: [[f32; 2]; 6] = [
+    [-0.5,  0.5],
+    [-0.5, -0.5],
+    [ 0.5, -0.5],
+    [-0.5,  0.5],
+    [ 0.5, -0.5],
+    [ 0.5,  0.5]
+];
+
+// Layout of the streaming vertex buffer: world-space position, texture
+// coordinates, then the tint.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4]
+}
+
+// A sprite as the batcher sees it, in its local space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quad {
+    pub width: f32,
+    pub height: f32,
+    // Local point put at the origin, from (0, 0) bottom-left to (1, 1) top-right.
+    pub pivot: [f32; 2],
+    pub tex_coords: [f32; 12],
+    pub color: [f32; 4]
+}
+
+// Sprites sharing a key are drawn by the same draw call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BatchKey {
+    pub shader: u32,
+    pub texture: u32
+}
+
+// A run of `vertex_count` vertices starting at `first_vertex`, all drawn with
+// the same shader and texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteBatch {
+    pub key: BatchKey,
+    pub first_vertex: usize,
+    pub vertex_count: usize
+}
+
+// Where a sprite is drawn relative to the others: by layer, then from the top
+// of the screen down on y-sorted layers, then by increasing z.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawOrder {
+    pub layer: RenderLayer,
+    // World y of the sprite when its layer is y-sorted.
+    pub y_sort: Option<f32>,
+    pub z: f32,
+    // Last resort between sprites that are otherwise equal, such as the
+    // entity index, so that they keep the same order from frame to frame.
+    pub tie_breaker: u32
+}
+
+impl DrawOrder {
+    pub fn new(layer: RenderLayer, z: f32) -> Self {
+        DrawOrder {
+            layer,
+            y_sort: None,
+            z,
+            tie_breaker: 0
+        }
+    }
+
+    fn cmp_depth(&self, other: &DrawOrder) -> Ordering {
+        self.layer.cmp(&other.layer)
+            .then_with(|| match (self.y_sort, other.y_sort) {
+                // Higher first
+                (Some(y), Some(other_y)) => other_y.total_cmp(&y),
+                _ => Ordering::Equal
+            })
+            .then_with(|| self.z.total_cmp(&other.z))
+    }
+}
+
+struct QueuedSprite {
+    order: DrawOrder,
+    key: BatchKey,
+    vertices: [SpriteVertex; 6]
+}
+
+// Collects the sprites of a frame, transforms their quads on the CPU, sorts
+// them and groups them into batches. Holds no GL state, the render system
+// uploads `vertices` and issues one draw per entry of `batches`.
+pub struct SpriteBatcher {
+    queued: Vec<QueuedSprite>,
+    vertices: Vec<SpriteVertex>,
+    batches: Vec<SpriteBatch>
+}
+
+impl Default for SpriteBatcher {
+    fn default() -> Self {
+        SpriteBatcher::new()
+    }
+}
+
+#[allow(dead_code)]
+impl SpriteBatcher {
+    pub fn new() -> Self {
+        SpriteBatcher {
+            queued: Vec::new(),
+            vertices: Vec::new(),
+            batches: Vec::new()
+        }
+    }
+
+    // Forgets the previous frame, keeping the allocations.
+    pub fn clear(&mut self) {
+        self.queued.clear();
+        self.vertices.clear();
+        self.batches.clear();
+    }
+
+    // Queues the quad, placed by `model`.
+    pub fn push(&mut self, order: DrawOrder, key: BatchKey, model: &Matrix4<f32>, quad: &Quad) {
+        let vertices = std::array::from_fn(|i| {
+            let [x, y] = QUAD_POSITIONS[i];
+            let local_x = (x + 0.5 - quad.pivot[0]) * quad.width;
+            let local_y = (y + 0.5 - quad.pivot[1]) * quad.height;
+            let position = model * Vector4::new(local_x, local_y, 0.0, 1.0);
+
+            SpriteVertex {
+                position: [position.x, position.y, position.z],
+                tex_coords: [quad.tex_coords[i * 2], quad.tex_coords[i * 2 + 1]],
+                color: quad.color
+            }
+        });
+
+        self.queued.push(QueuedSprite { order, key, vertices });
+    }
+
+    // Sorts the queued sprites by draw order, then merges neighbours sharing a
+    // key into batches. The key only separates sprites whose draw orders are
+    // equal, so that the result does not depend on the order of the `push`
+    // calls.
+    pub fn build(&mut self) {
+        self.queued.sort_by(|a, b| {
+            a.order.cmp_depth(&b.order)
+                .then_with(|| a.order.tie_breaker.cmp(&b.order.tie_breaker))
+                .then_with(|| a.key.cmp(&b.key))
+        });
+
+        self.vertices.clear();
+        self.batches.clear();
+
+        for sprite in &self.queued {
+            match self.batches.last_mut() {
+                Some(batch) if batch.key == sprite.key => batch.vertex_count += 6,
+                _ => self.batches.push(SpriteBatch {
+                    key: sprite.key,
+                    first_vertex: self.vertices.len(),
+                    vertex_count: 6
+                })
+            }
+
+            self.vertices.extend_from_slice(&sprite.vertices);
+        }
+    }
+
+    pub fn vertices(&self) -> &[SpriteVertex] {
+        &self.vertices
+    }
+
+    pub fn batches(&self) -> &[SpriteBatch] {
+        &self.batches
+    }
+
+    pub fn sprite_count(&self) -> usize {
+        self.queued.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::SquareMatrix;
+
+    fn quad() -> Quad {
+        Quad {
+            width: 2.0,
+            height: 2.0,
+            pivot: [0.5, 0.5],
+            tex_coords: [0.0; 12],
+            color: [1.0; 4]
+        }
+    }
+
+    fn key(texture: u32) -> BatchKey {
+        BatchKey { shader: 1, texture }
+    }
+
+    #[test]
+    fn one_batch_per_texture_run() {
+        let mut batcher = SpriteBatcher::new();
+        for (z, texture) in [1, 1, 2, 2, 2, 1].into_iter().enumerate() {
+            batcher.push(DrawOrder::new(RenderLayer::DEFAULT, z as f32), key(texture), &Matrix4::identity(), &quad());
+        }
+        batcher.build();
+
+        let runs: Vec<(u32, usize, usize)> = batcher.batches().iter()
+            .map(|batch| (batch.key.texture, batch.first_vertex, batch.vertex_count))
+            .collect();
+        assert_eq!(runs, [(1, 0, 12), (2, 12, 18), (1, 30, 6)]);
+    }
+
+    #[test]
+    fn six_vertices_per_sprite() {
+        let mut batcher = SpriteBatcher::new();
+        for index in 0..5 {
+            let model = Matrix4::from_translation(cgmath::vec3(index as f32 * 10.0, 0.0, 0.0));
+            batcher.push(DrawOrder::new(RenderLayer::DEFAULT, 0.0), key(index % 2), &model, &quad());
+        }
+        batcher.build();
+
+        assert_eq!(batcher.sprite_count(), 5);
+        assert_eq!(batcher.vertices().len(), 6 * 5);
+        assert_eq!(batcher.batches().iter().map(|batch| batch.vertex_count).sum::<usize>(), 6 * 5);
+
+        // Quads are placed in world space around their pivot
+        let first = &batcher.vertices()[0..6];
+        assert_eq!(first[0].position, [-1.0, 1.0, 0.0]);
+        assert_eq!(first[2].position, [1.0, -1.0, 0.0]);
+    }
+
+    fn build_textures(sprites: &[(DrawOrder, u32)]) -> Vec<u32> {
+        let mut batcher = SpriteBatcher::new();
+        for (order, texture) in sprites {
+            batcher.push(*order, key(*texture), &Matrix4::identity(), &quad());
+        }
+        batcher.build();
+
+        batcher.batches().iter()
+            .flat_map(|batch| std::iter::repeat_n(batch.key.texture, batch.vertex_count / 6))
+            .collect()
+    }
+
+    fn order(layer: u16, y_sort: Option<f32>, z: f32, tie_breaker: u32) -> DrawOrder {
+        DrawOrder { layer: RenderLayer(layer), y_sort, z, tie_breaker }
+    }
+
+    #[test]
+    fn push_order_does_not_matter() {
+        let sprites = [
+            (order(0, None, 0.0, 3), 1),
+            (order(0, None, 0.0, 1), 2),
+            (order(0, None, 0.0, 2), 1),
+            (order(0, None, -1.0, 4), 2),
+            (order(1, None, 0.0, 0), 3)
+        ];
+        let mut reversed = sprites;
+        reversed.reverse();
+
+        let expected = [2, 2, 1, 1, 3];
+        assert_eq!(build_textures(&sprites), expected);
+        assert_eq!(build_textures(&reversed), expected);
+    }
+
+    #[test]
+    fn tie_breaker_comes_before_the_key() {
+        // Grouping by texture would save a draw call but swap sprites 1 and 2
+        let sprites = [
+            (order(0, None, 0.0, 0), 1),
+            (order(0, None, 0.0, 1), 2),
+            (order(0, None, 0.0, 2), 1)
+        ];
+        assert_eq!(build_textures(&sprites), [1, 2, 1]);
+    }
+
+    #[test]
+    fn y_sorted_sprites_are_drawn_from_the_top_down() {
+        let sprites = [
+            (order(0, Some(-5.0), 0.0, 0), 1),
+            (order(0, Some(10.0), 0.0, 1), 2),
+            (order(0, Some(0.0), 0.0, 2), 3)
+        ];
+        assert_eq!(build_textures(&sprites), [2, 3, 1]);
+
+        // z only separates sprites at the same height
+        let sprites = [
+            (order(0, Some(1.0), 5.0, 0), 1),
+            (order(0, Some(1.0), -5.0, 1), 2),
+            (order(0, Some(2.0), 9.0, 2), 3)
+        ];
+        assert_eq!(build_textures(&sprites), [3, 2, 1]);
+    }
+
+    #[test]
+    fn layers_take_precedence() {
+        let sprites = [
+            (order(2, None, -100.0, 0), 1),
+            (order(0, Some(-100.0), 100.0, 1), 2),
+            (order(1, Some(100.0), 0.0, 2), 3)
+        ];
+        assert_eq!(build_textures(&sprites), [2, 3, 1]);
+    }
+
+    #[test]
+    fn clear_forgets_the_previous_frame() {
+        let mut batcher = SpriteBatcher::new();
+        batcher.push(DrawOrder::new(RenderLayer::DEFAULT, 0.0), key(1), &Matrix4::identity(), &quad());
+        batcher.build();
+
+        batcher.clear();
+        batcher.build();
+        assert_eq!(batcher.sprite_count(), 0);
+        assert!(batcher.vertices().is_empty());
+        assert!(batcher.batches().is_empty());
+    }
+}
@@ -0,0 +1,28 @@
+use cgmath::Vector3;
+use crate::graphics::{animation::AnimationComponent, sprite::{SpriteCreator, SpriteRendererComponent}, spritesheet::Spritesheet};
+use crate::world::bundle::Bundle;
+use crate::world::components::TransformComponent;
+
+// Everything an animated sprite needs to be drawn: spawn it alone, or nest it
+// in a game-specific bundle next to gameplay components.
+#[derive(Bundle)]
+pub struct SpriteActorBundle {
+    pub transform: TransformComponent,
+    pub sprite: SpriteRendererComponent,
+    pub animation: AnimationComponent
+}
+
+impl SpriteActorBundle {
+    // Positioned sprite taken from `spritesheet`, already playing `animation`.
+    pub fn from_sprite(spritesheet: &Spritesheet, sprite_name: &str, position: Vector3<f32>, animation: &str) -> Option<Self> {
+        let sprite = SpriteCreator::from_sprite(spritesheet, sprite_name)?;
+
+        let mut transform = TransformComponent::new();
+        transform.transform.set_local_position(position);
+
+        let mut animation_comp = AnimationComponent::new();
+        animation_comp.play(animation);
+
+        Some(SpriteActorBundle { transform, sprite, animation: animation_comp })
+    }
+}
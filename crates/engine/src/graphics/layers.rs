@@ -0,0 +1,103 @@
+// Index of a layer in `RenderLayers`. Lower layers are drawn first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RenderLayer(pub u16);
+
+impl RenderLayer {
+    pub const DEFAULT: RenderLayer = RenderLayer(0);
+}
+
+#[derive(Clone, Debug)]
+pub struct LayerSettings {
+    pub name: String,
+    // Sprites higher on screen are drawn first, so that in a top-down view
+    // what stands in front covers what stands behind.
+    pub y_sort: bool
+}
+
+// The named layers of the game, in drawing order. Starts with a "default"
+// layer, which is not y-sorted.
+pub struct RenderLayers {
+    layers: Vec<LayerSettings>
+}
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        RenderLayers::new()
+    }
+}
+
+#[allow(dead_code)]
+impl RenderLayers {
+    pub fn new() -> Self {
+        RenderLayers {
+            layers: vec![LayerSettings { name: "default".to_string(), y_sort: false }]
+        }
+    }
+
+    // Adds a layer drawn above every existing one.
+    pub fn add(&mut self, name: &str, y_sort: bool) -> RenderLayer {
+        if let Some(layer) = self.get(name) {
+            eprintln!("Render layer '{}' already exists", name);
+            return layer;
+        }
+
+        self.layers.push(LayerSettings { name: name.to_string(), y_sort });
+        RenderLayer((self.layers.len() - 1) as u16)
+    }
+
+    pub fn get(&self, name: &str) -> Option<RenderLayer> {
+        self.layers.iter()
+            .position(|layer| layer.name == name)
+            .map(|index| RenderLayer(index as u16))
+    }
+
+    pub fn settings(&self, layer: RenderLayer) -> Option<&LayerSettings> {
+        self.layers.get(layer.0 as usize)
+    }
+
+    pub fn is_y_sorted(&self, layer: RenderLayer) -> bool {
+        self.settings(layer).is_some_and(|settings| settings.y_sort)
+    }
+
+    // At least one: the default layer.
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_are_drawn_in_the_order_they_are_added() {
+        let mut layers = RenderLayers::new();
+        let ground = layers.add("ground", false);
+        let world = layers.add("world", true);
+
+        assert_eq!(layers.get("default"), Some(RenderLayer::DEFAULT));
+        assert!(RenderLayer::DEFAULT < ground && ground < world);
+        assert!(layers.is_y_sorted(world));
+        assert!(!layers.is_y_sorted(ground));
+        assert_eq!(layers.layer_count(), 3);
+    }
+
+    #[test]
+    fn duplicate_name_returns_the_existing_layer() {
+        let mut layers = RenderLayers::new();
+        let world = layers.add("world", true);
+
+        assert_eq!(layers.add("world", false), world);
+        assert_eq!(layers.layer_count(), 2);
+        // The settings of the first one are kept
+        assert!(layers.is_y_sorted(world));
+    }
+
+    #[test]
+    fn unknown_names_and_layers() {
+        let layers = RenderLayers::new();
+        assert_eq!(layers.get("ui"), None);
+        assert!(layers.settings(RenderLayer(4)).is_none());
+        assert!(!layers.is_y_sorted(RenderLayer(4)));
+    }
+}
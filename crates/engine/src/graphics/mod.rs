@@ -1,4 +1,8 @@
 pub mod sprite;
 pub mod spritesheet;
 pub mod managers;
-pub mod animation;
\ No newline at end of file
+pub mod animation;
+pub mod bundles;
+pub mod batch;
+pub mod layers;
+pub mod postprocess;
\ No newline at end of file
@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::raw::c_void;
+use std::{fs::File, io::Read};
+
+use cgmath::{Vector2, Vector3, Vector4};
+use gl::types::*;
+use serde::Deserialize;
+
+use crate::c_str;
+use crate::core::path::get_path_to_asset;
+use crate::glutils::buffer::{VertexArray, VertexBuffer};
+use crate::glutils::framebuffer::Framebuffer;
+use crate::glutils::shader::Shader;
+
+const DEFAULT_VERTEX_SHADER: &str = "shaders/post/fullscreen.vs";
+
+// Uniform value of a pass, written in JSON as a number or an array.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum PassParam {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4])
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PostProcessPassSerializer {
+    pub name: String,
+    // Defaults to the full-screen quad shader every pass can share.
+    #[serde(default)]
+    pub vertex: Option<String>,
+    pub fragment: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub params: BTreeMap<String, PassParam>
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+// A full-screen shader reading the previous pass from `screen_texture`. It
+// also gets `resolution` (pixels) and `time` (seconds), plus its params.
+pub struct PostProcessPass {
+    name: String,
+    shader: Shader,
+    pub enabled: bool,
+    params: BTreeMap<String, PassParam>
+}
+
+#[allow(dead_code)]
+impl PostProcessPass {
+    pub fn from_serializer(serializer: PostProcessPassSerializer) -> Self {
+        let vertex = serializer.vertex.as_deref().unwrap_or(DEFAULT_VERTEX_SHADER);
+
+        PostProcessPass {
+            name: serializer.name,
+            shader: Shader::new(vertex, &serializer.fragment),
+            enabled: serializer.enabled,
+            params: serializer.params
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn param(&self, name: &str) -> Option<PassParam> {
+        self.params.get(name).copied()
+    }
+
+    pub fn set_param(&mut self, name: &str, value: PassParam) {
+        self.params.insert(name.to_string(), value);
+    }
+
+    unsafe fn apply_params(&self) {
+        for (name, value) in &self.params {
+            let Ok(uniform) = CString::new(name.as_str()) else {
+                continue;
+            };
+
+            unsafe {
+                match *value {
+                    PassParam::Float(x) => self.shader.set_float(&uniform, x),
+                    PassParam::Vec2([x, y]) => self.shader.set_vec2(&uniform, &Vector2::new(x, y)),
+                    PassParam::Vec3([x, y, z]) => self.shader.set_vec3(&uniform, &Vector3::new(x, y, z)),
+                    PassParam::Vec4([x, y, z, w]) => self.shader.set_vec4(&uniform, &Vector4::new(x, y, z, w))
+                }
+            }
+        }
+    }
+}
+
+// Screen effects applied in order after the world is drawn. While at least one
+// pass is enabled, the application renders the frame into an offscreen target
+// and each pass draws the previous result, the last one to the window.
+pub struct PostProcessStack {
+    passes: Vec<PostProcessPass>,
+    // Ping-pong targets, created on first use and resized with the window.
+    targets: Vec<Framebuffer>,
+    quad: Option<(VertexArray, VertexBuffer)>
+}
+
+impl Default for PostProcessStack {
+    fn default() -> Self {
+        PostProcessStack::new()
+    }
+}
+
+#[allow(dead_code)]
+impl PostProcessStack {
+    pub fn new() -> Self {
+        PostProcessStack {
+            passes: Vec::new(),
+            targets: Vec::new(),
+            quad: None
+        }
+    }
+
+    // Loads a pass description and appends it to the stack.
+    pub fn load(&mut self, metadata_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = File::open(get_path_to_asset(metadata_path))?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let serializer: PostProcessPassSerializer = serde_json::from_str(&contents)?;
+
+        self.add(PostProcessPass::from_serializer(serializer));
+        Ok(())
+    }
+
+    // A pass with the same name is replaced in place.
+    pub fn add(&mut self, pass: PostProcessPass) {
+        match self.passes.iter_mut().find(|existing| existing.name == pass.name) {
+            Some(existing) => *existing = pass,
+            None => self.passes.push(pass)
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<PostProcessPass> {
+        let index = self.passes.iter().position(|pass| pass.name == name)?;
+        Some(self.passes.remove(index))
+    }
+
+    pub fn pass(&self, name: &str) -> Option<&PostProcessPass> {
+        self.passes.iter().find(|pass| pass.name == name)
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut PostProcessPass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        match self.pass_mut(name) {
+            Some(pass) => pass.enabled = enabled,
+            None => eprintln!("No post-process pass named '{}'", name)
+        }
+    }
+
+    pub fn set_param(&mut self, pass: &str, param: &str, value: PassParam) {
+        match self.pass_mut(pass) {
+            Some(pass) => pass.set_param(param, value),
+            None => eprintln!("No post-process pass named '{}'", pass)
+        }
+    }
+
+    pub fn passes(&self) -> impl Iterator<Item = &PostProcessPass> {
+        self.passes.iter()
+    }
+
+    pub fn has_enabled_passes(&self) -> bool {
+        self.passes.iter().any(|pass| pass.enabled)
+    }
+
+    // Binds the target the world is drawn into. Returns false when it could
+    // not be created, in which case the frame is drawn without effects.
+    pub(crate) fn begin(&mut self, width: u32, height: u32) -> bool {
+        let outdated = self.targets.first().is_none_or(|target| target.width() != width || target.height() != height);
+        if outdated {
+            self.targets.clear();
+            for _ in 0..2 {
+                match Framebuffer::new(width, height) {
+                    Ok(target) => self.targets.push(target),
+                    Err(e) => {
+                        eprintln!("Post-processing disabled: {}", e);
+                        self.targets.clear();
+                        return false;
+                    }
+                }
+            }
+        }
+
+        self.targets[0].bind();
+        true
+    }
+
+    // Runs the enabled passes over what was drawn since `begin`, ending on
+    // the window's framebuffer.
+    pub(crate) fn finish(&mut self, width: u32, height: u32, time: f32) {
+        let (vao, _) = self.quad.get_or_insert_with(create_quad);
+        let enabled: Vec<&PostProcessPass> = self.passes.iter().filter(|pass| pass.enabled).collect();
+
+        unsafe {
+            // Each pass replaces the whole target
+            gl::Disable(gl::BLEND);
+            gl::ActiveTexture(gl::TEXTURE0);
+            vao.bind();
+
+            let mut source = 0;
+            for (index, pass) in enabled.iter().enumerate() {
+                if index + 1 == enabled.len() {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                    gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+                } else {
+                    self.targets[1 - source].bind();
+                }
+
+                pass.shader.use_program();
+                pass.shader.set_int(c_str!("screen_texture"), 0);
+                pass.shader.set_vec2(c_str!("resolution"), &Vector2::new(width as f32, height as f32));
+                pass.shader.set_float(c_str!("time"), time);
+                pass.apply_params();
+
+                gl::BindTexture(gl::TEXTURE_2D, self.targets[source].texture().id);
+                gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+                source = 1 - source;
+            }
+
+            vao.unbind();
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::Enable(gl::BLEND);
+        }
+    }
+}
+
+// Two triangles covering the screen: clip-space position, then UVs.
+fn create_quad() -> (VertexArray, VertexBuffer) {
+    let vertices: [f32; 24] = [
+        -1.0,  1.0,    0.0, 1.0,
+        -1.0, -1.0,    0.0, 0.0,
+         1.0, -1.0,    1.0, 0.0,
+
+        -1.0,  1.0,    0.0, 1.0,
+         1.0, -1.0,    1.0, 0.0,
+         1.0,  1.0,    1.0, 1.0,
+    ];
+
+    let vao = VertexArray::new();
+    let vbo = VertexBuffer::new();
+
+    vao.bind();
+    vbo.bind();
+    vbo.set_data(&vertices);
+
+    let stride = 4 * std::mem::size_of::<GLfloat>() as GLsizei;
+    vao.set_attribute(0, 2, gl::FLOAT, stride, std::ptr::null());
+    vao.set_attribute(1, 2, gl::FLOAT, stride, (2 * std::mem::size_of::<GLfloat>()) as *const c_void);
+
+    vbo.unbind();
+    vao.unbind();
+
+    (vao, vbo)
+}
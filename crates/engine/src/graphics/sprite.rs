@@ -1,111 +1,289 @@
-use std::os::raw::c_void;
-use gl::types::*;
-use crate::{glutils::{
-    buffer::{VertexArray, VertexBuffer},
-    texture::Texture
-}, graphics::spritesheet::Spritesheet};
+use crate::{glutils::texture::Texture, graphics::{batch::Quad, layers::{RenderLayer, RenderLayers}, managers::SpritesheetManager, spritesheet::{tex_coords_from_pixels, Spritesheet}}};
 use crate::world::components::Component;
+use serde::Deserialize;
+
+// Part of a texture, in pixels from its top-left corner like the sprites of a
+// spritesheet.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
 
-#[derive(Default)]
 #[allow(dead_code)]
 pub struct SpriteRendererComponent {
-    pub vao: VertexArray,
-    pub vbo: VertexBuffer,
     pub texture: Texture,
+    // UVs of the six vertices of the quad, see `batch::QUAD_POSITIONS`.
+    pub tex_coords: [f32; 12],
     pub width: u32,
-    pub height: u32
+    pub height: u32,
+    pub layer: RenderLayer,
+    // Order inside the layer, after the y-sort if the layer has one.
+    pub z: f32,
+    // RGBA multiplied with the texture: white and opaque by default.
+    pub color: [f32; 4],
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // Point of the sprite placed on the transform position, from (0, 0) at
+    // the bottom-left corner to (1, 1) at the top-right one.
+    pub pivot: [f32; 2],
+    // Drawn instead of `tex_coords`, at its own size, when set.
+    pub source_rect: Option<SourceRect>
 }
 
 impl Component for SpriteRendererComponent {}
 
-pub struct SpriteCreator;
+#[allow(dead_code)]
+impl SpriteRendererComponent {
+    pub fn new(texture: Texture, tex_coords: [f32; 12], width: u32, height: u32) -> Self {
+        SpriteRendererComponent {
+            texture,
+            tex_coords,
+            width,
+            height,
+            layer: RenderLayer::DEFAULT,
+            z: 0.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            flip_x: false,
+            flip_y: false,
+            pivot: [0.5, 0.5],
+            source_rect: None
+        }
+    }
 
-impl SpriteCreator {
-    pub fn from_texture(texture_path: &str) -> SpriteRendererComponent {
-        let vertices: [f32; 24] = [
-            //   Positions     TexCoords
-            -0.5,  0.5,    0.0, 1.0, // Haut-gauche
-            -0.5, -0.5,    0.0, 0.0, // Bas-gauche
-             0.5, -0.5,    1.0, 0.0, // Bas-droit
-
-            -0.5,  0.5,    0.0, 1.0, // Haut-gauche
-             0.5, -0.5,    1.0, 0.0, // Bas-droit
-             0.5,  0.5,    1.0, 1.0, // Haut-droit
-        ];
+    pub fn set_tint(&mut self, red: f32, green: f32, blue: f32) {
+        self.color[0] = red;
+        self.color[1] = green;
+        self.color[2] = blue;
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.color[3]
+    }
+
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.color[3] = opacity.clamp(0.0, 1.0);
+    }
+
+    // What the renderer draws: the size and UVs once the source rect and the
+    // flips are applied.
+    pub fn quad(&self) -> Quad {
+        let (mut tex_coords, width, height) = match self.source_rect {
+            Some(rect) => (
+                tex_coords_from_pixels(rect.x, rect.y, rect.width, rect.height, self.texture.width, self.texture.height),
+                rect.width,
+                rect.height
+            ),
+            None => (self.tex_coords, self.width, self.height)
+        };
+
+        if self.flip_x {
+            mirror_tex_coords(&mut tex_coords, 0);
+        }
+        if self.flip_y {
+            mirror_tex_coords(&mut tex_coords, 1);
+        }
+
+        Quad {
+            width: width as f32,
+            height: height as f32,
+            pivot: self.pivot,
+            tex_coords,
+            color: self.color
+        }
+    }
+}
+
+// Swaps the smallest and largest value of one UV component (0 for u, 1 for v)
+// across the quad.
+fn mirror_tex_coords(tex_coords: &mut [f32; 12], component: usize) {
+    let values = tex_coords.iter().skip(component).step_by(2);
+    let min = values.clone().copied().fold(f32::INFINITY, f32::min);
+    let max = values.copied().fold(f32::NEG_INFINITY, f32::max);
+
+    for value in tex_coords.iter_mut().skip(component).step_by(2) {
+        *value = min + max - *value;
+    }
+}
+
+// Prefab description of a sprite: either a whole texture, or a named sprite
+// from a spritesheet that is already loaded.
+#[derive(Deserialize, Debug)]
+pub struct SpriteSerializer {
+    #[serde(default)]
+    pub texture: Option<String>,
+    #[serde(default)]
+    pub spritesheet: Option<String>,
+    #[serde(default)]
+    pub sprite: Option<String>,
+    // Name of a layer of the `RenderLayers` resource.
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub z: f32,
+    #[serde(default)]
+    pub color: Option<[f32; 4]>,
+    #[serde(default)]
+    pub flip_x: bool,
+    #[serde(default)]
+    pub flip_y: bool,
+    #[serde(default)]
+    pub pivot: Option<[f32; 2]>,
+    #[serde(default)]
+    pub source_rect: Option<SourceRect>
+}
 
-        let vao = VertexArray::new();
-        let vbo = VertexBuffer::new();
+impl SpriteSerializer {
+    pub fn to_component(&self, spritesheets: &SpritesheetManager, layers: &RenderLayers) -> Result<SpriteRendererComponent, String> {
+        let layer = match &self.layer {
+            Some(name) => layers.get(name).ok_or_else(|| format!("no render layer named '{}'", name))?,
+            None => RenderLayer::DEFAULT
+        };
 
-        vao.bind();
-        vbo.bind();
-        vbo.set_data(&vertices);
+        let mut sprite = match (&self.texture, &self.spritesheet, &self.sprite) {
+            (Some(texture_path), None, None) => Ok(SpriteCreator::from_texture(texture_path)),
+            (None, Some(spritesheet_name), Some(sprite_name)) => {
+                let spritesheet = spritesheets.get(spritesheet_name)
+                    .ok_or_else(|| format!("spritesheet '{}' is not loaded", spritesheet_name))?;
+                SpriteCreator::from_sprite(spritesheet, sprite_name)
+                    .ok_or_else(|| format!("no sprite '{}' in spritesheet '{}'", sprite_name, spritesheet_name))
+            }
+            _ => Err("expected either \"texture\" or both \"spritesheet\" and \"sprite\"".to_string())
+        }?;
 
-        let stride = 4 * std::mem::size_of::<GLfloat>() as GLsizei;
+        sprite.layer = layer;
+        sprite.z = self.z;
+        sprite.color = self.color.unwrap_or(sprite.color);
+        sprite.flip_x = self.flip_x;
+        sprite.flip_y = self.flip_y;
+        sprite.pivot = self.pivot.unwrap_or(sprite.pivot);
+        sprite.source_rect = self.source_rect;
+        Ok(sprite)
+    }
+}
 
-        vao.set_attribute(0, 2, gl::FLOAT, stride, std::ptr::null());
-        vao.set_attribute(1, 2, gl::FLOAT, stride, (2 * std::mem::size_of::<GLfloat>()) as *const c_void);
+pub struct SpriteCreator;
 
-        vbo.unbind();
-        vao.unbind();
+impl SpriteCreator {
+    pub fn from_texture(texture_path: &str) -> SpriteRendererComponent {
+        let tex_coords: [f32; 12] = [
+            0.0, 1.0, // Haut-gauche
+            0.0, 0.0, // Bas-gauche
+            1.0, 0.0, // Bas-droit
+
+            0.0, 1.0, // Haut-gauche
+            1.0, 0.0, // Bas-droit
+            1.0, 1.0, // Haut-droit
+        ];
 
         let texture = Texture::new(texture_path);
 
         let width = texture.width;
         let height = texture.height;
 
-        SpriteRendererComponent {
-            vao,
-            vbo,
-            texture,
-            width,
-            height 
-        }
+        SpriteRendererComponent::new(texture, tex_coords, width, height)
     }
 
     pub fn from_sprite(spritesheet: &Spritesheet, sprite_name: &str) -> Option<SpriteRendererComponent> {
         let sprite_data = spritesheet.get_sprite(sprite_name)?;
 
-        let positions: [f32; 12] = [
-            // Triangle 1
-            -0.5,  0.5, // Haut-gauche
-            -0.5, -0.5, // Bas-gauche
-             0.5, -0.5, // Bas-droit
-             // Triangle 2
-            -0.5,  0.5, // Haut-gauche
-             0.5, -0.5, // Bas-droit
-             0.5,  0.5, // Haut-droit
-        ];
+        Some(SpriteRendererComponent::new(spritesheet.texture.clone(), sprite_data.tex_coords, sprite_data.width, sprite_data.height))
+    }
+}
 
-        let mut vertices = [0.0f32; 24];
-        for i in 0..6 {
-            vertices[i * 4] = positions[i * 2];         // Pos X
-            vertices[i * 4 + 1] = positions[i * 2 + 1]; // Pos Y
-            vertices[i * 4 + 2] = sprite_data.tex_coords[i * 2];     // Tex U
-            vertices[i * 4 + 3] = sprite_data.tex_coords[i * 2 + 1]; // Tex V
-        }
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix4, SquareMatrix};
 
-        let vao = VertexArray::new();
-        let vbo = VertexBuffer::new();
+    use super::*;
+    use crate::graphics::batch::{BatchKey, DrawOrder, SpriteBatcher};
 
-        vao.bind();
-        vbo.bind();
-        vbo.set_data(&vertices);
+    // Whole texture: u from 0 to 1, v from 1 at the top to 0 at the bottom.
+    const FULL: [f32; 12] = [
+        0.0, 1.0,
+        0.0, 0.0,
+        1.0, 0.0,
 
-        let stride = 4 * std::mem::size_of::<GLfloat>() as GLsizei;
+        0.0, 1.0,
+        1.0, 0.0,
+        1.0, 1.0
+    ];
 
-        // position
-        vao.set_attribute(0, 2, gl::FLOAT, stride, std::ptr::null());
-        vao.set_attribute(1, 2, gl::FLOAT, stride, (2 * std::mem::size_of::<GLfloat>()) as *const c_void);
+    fn sprite() -> SpriteRendererComponent {
+        let texture = Texture { width: 64, height: 32, ..Texture::default() };
+        SpriteRendererComponent::new(texture, FULL, 20, 10)
+    }
 
-        vbo.unbind();
-        vao.unbind();
+    // (u, v) of each vertex.
+    fn uvs(quad: &Quad) -> Vec<(f32, f32)> {
+        quad.tex_coords.chunks_exact(2).map(|uv| (uv[0], uv[1])).collect()
+    }
+
+    #[test]
+    fn unflipped_quad_keeps_the_sprite() {
+        let quad = sprite().quad();
+        assert_eq!(quad.tex_coords, FULL);
+        assert_eq!((quad.width, quad.height), (20.0, 10.0));
+        assert_eq!(quad.pivot, [0.5, 0.5]);
+        assert_eq!(quad.color, [1.0; 4]);
+    }
+
+    #[test]
+    fn flip_x_mirrors_u_only() {
+        let mut sprite = sprite();
+        sprite.flip_x = true;
+        assert_eq!(uvs(&sprite.quad()), [(1.0, 1.0), (1.0, 0.0), (0.0, 0.0), (1.0, 1.0), (0.0, 0.0), (0.0, 1.0)]);
+    }
+
+    #[test]
+    fn flip_y_mirrors_v_only() {
+        let mut sprite = sprite();
+        sprite.flip_y = true;
+        assert_eq!(uvs(&sprite.quad()), [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.0, 0.0), (1.0, 1.0), (1.0, 0.0)]);
+    }
+
+    #[test]
+    fn both_flips_turn_the_sprite_upside_down() {
+        let mut sprite = sprite();
+        sprite.flip_x = true;
+        sprite.flip_y = true;
+        assert_eq!(uvs(&sprite.quad()), [(1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (1.0, 0.0), (0.0, 1.0), (0.0, 0.0)]);
+    }
+
+    #[test]
+    fn source_rect_maps_pixels_to_uvs() {
+        let mut sprite = sprite();
+        sprite.source_rect = Some(SourceRect { x: 16, y: 8, width: 16, height: 8 });
+
+        let quad = sprite.quad();
+        assert_eq!((quad.width, quad.height), (16.0, 8.0));
+        // The top row of the rect is y = 8, its bottom row y = 16
+        assert_eq!(uvs(&quad), [(0.25, 0.25), (0.25, 0.5), (0.5, 0.5), (0.25, 0.25), (0.5, 0.5), (0.5, 0.25)]);
+
+        // Flips stay inside the rect
+        sprite.flip_x = true;
+        assert_eq!(uvs(&sprite.quad())[0], (0.5, 0.25));
+    }
+
+    #[test]
+    fn pivot_is_put_on_the_transform_position() {
+        let corners = |pivot: [f32; 2]| {
+            let mut sprite = sprite();
+            sprite.pivot = pivot;
 
-        Some(SpriteRendererComponent {
-            vao,
-            vbo,
-            texture: spritesheet.texture.clone(),
-            width: sprite_data.width,
-            height: sprite_data.height
-        })
+            let mut batcher = SpriteBatcher::new();
+            batcher.push(DrawOrder::new(RenderLayer::DEFAULT, 0.0), BatchKey { shader: 0, texture: 0 }, &Matrix4::identity(), &sprite.quad());
+            batcher.build();
+            // Bottom-left, then top-right
+            let vertices = batcher.vertices();
+            ([vertices[1].position[0], vertices[1].position[1]], [vertices[5].position[0], vertices[5].position[1]])
+        };
+
+        assert_eq!(corners([0.5, 0.5]), ([-10.0, -5.0], [10.0, 5.0]));
+        assert_eq!(corners([0.0, 0.0]), ([0.0, 0.0], [20.0, 10.0]));
+        assert_eq!(corners([0.5, 0.0]), ([-10.0, 0.0], [10.0, 10.0]));
+        assert_eq!(corners([1.0, 1.0]), ([-20.0, -10.0], [0.0, 0.0]));
     }
-}
\ No newline at end of file
+}
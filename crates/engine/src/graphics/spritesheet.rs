@@ -30,6 +30,34 @@ pub struct Sprite {
     pub height: u32
 }
 
+// UVs of the quad vertices (see `batch::QUAD_POSITIONS`) showing the given
+// pixels of a `texture_width` x `texture_height` texture.
+pub fn tex_coords_from_pixels(x: u32, y: u32, width: u32, height: u32, texture_width: u32, texture_height: u32) -> [f32; 12] {
+    let tex_width = texture_width as f32;
+    let tex_height = texture_height as f32;
+
+    let left = x as f32;
+    let right = (x + width) as f32;
+    let top = y as f32;
+    let bottom = (y + height) as f32;
+
+    let u_min = left / tex_width;
+    let u_max = right / tex_width;
+    let v_min = bottom / tex_height;
+    let v_max = top / tex_height;
+
+    [
+        // Triangle 1
+        u_min, v_max, // Haut-gauche
+        u_min, v_min, // Bas-gauche
+        u_max, v_min, // Bas-droit
+        // Triangle 2
+        u_min, v_max, // Haut-gauche
+        u_max, v_min, // Bas-droit
+        u_max, v_max, // Haut-droit
+    ]
+}
+
 #[derive(Debug)]
 pub struct Spritesheet {
     pub name: String,
@@ -50,31 +78,10 @@ impl Spritesheet {
 
     pub fn from_serializer(serializer: SpritesheetSerializer) -> Result<Self, Box<dyn std::error::Error>> {
         let texture = Texture::new(&serializer.texture);
-        let tex_width = texture.width as f32;
-        let tex_height = texture.height as f32;
 
         let mut sprites = HashMap::new();
         for s_data in serializer.sprites {
-            let left = s_data.x as f32;
-            let right = (s_data.x + s_data.width) as f32;
-            let top = s_data.y as f32;
-            let bottom = (s_data.y + s_data.height) as f32;
-
-            let u_min = left / tex_width;
-            let u_max = right / tex_width;
-            let v_min = bottom / tex_height;
-            let v_max = top / tex_height;
-
-            let tex_coords = [
-                // Triangle 1
-                u_min, v_max, // Haut-gauche
-                u_min, v_min, // Bas-gauche
-                u_max, v_min, // Bas-droit
-                // Triangle 2
-                u_min, v_max, // Haut-gauche
-                u_max, v_min, // Bas-droit
-                u_max, v_max, // Haut-droit
-            ];
+            let tex_coords = tex_coords_from_pixels(s_data.x, s_data.y, s_data.width, s_data.height, texture.width, texture.height);
 
             sprites.insert(
                 s_data.name.clone(),
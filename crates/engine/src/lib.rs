@@ -1,3 +1,6 @@
+// Lets `#[derive(Bundle)]` refer to `::engine` from inside this crate too.
+extern crate self as engine;
+
 pub mod core;
 pub mod glutils;
 pub mod graphics;
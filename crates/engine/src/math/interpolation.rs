@@ -0,0 +1,174 @@
+use std::f32::consts::{PI, TAU};
+
+use cgmath::{Quaternion, VectorSpace};
+
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// Where `value` sits between `a` and `b`, 0 at `a` and 1 at `b`.
+pub fn inverse_lerp(a: f32, b: f32, value: f32) -> f32 {
+    if a == b {
+        return 0.0;
+    }
+    (value - a) / (b - a)
+}
+
+pub fn remap(value: f32, from: (f32, f32), to: (f32, f32)) -> f32 {
+    lerp(to.0, to.1, inverse_lerp(from.0, from.1, value))
+}
+
+// Works for any cgmath vector type.
+pub fn lerp_vector<V: VectorSpace<Scalar = f32>>(a: V, b: V, t: f32) -> V {
+    a + (b - a) * t
+}
+
+// Interpolates angles in radians along the shortest way around the circle.
+pub fn lerp_angle(a: f32, b: f32, t: f32) -> f32 {
+    let mut delta = (b - a) % TAU;
+    if delta > PI {
+        delta -= TAU;
+    } else if delta < -PI {
+        delta += TAU;
+    }
+    a + delta * t
+}
+
+pub fn slerp(a: Quaternion<f32>, b: Quaternion<f32>, t: f32) -> Quaternion<f32> {
+    a.slerp(b, t)
+}
+
+// Frame-rate independent exponential approach of `current` to `target`:
+// `sharpness` is how much of the remaining distance is covered per second, in [0, 1).
+pub fn damp(current: f32, target: f32, sharpness: f32, delta_time: f32) -> f32 {
+    lerp(current, target, 1.0 - (1.0 - sharpness).powf(delta_time))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    BackOut,
+    BounceOut
+}
+
+impl Easing {
+    // Maps a progress `t` in [0, 1] (clamped) onto the curve.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 { 2.0 * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0 }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0 }
+            }
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+            Easing::BackOut => {
+                let c1 = 1.70158;
+                let c3 = c1 + 1.0;
+                1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
+            }
+            Easing::BounceOut => {
+                let n1 = 7.5625;
+                let d1 = 2.75;
+                if t < 1.0 / d1 {
+                    n1 * t * t
+                } else if t < 2.0 / d1 {
+                    let t = t - 1.5 / d1;
+                    n1 * t * t + 0.75
+                } else if t < 2.5 / d1 {
+                    let t = t - 2.25 / d1;
+                    n1 * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d1;
+                    n1 * t * t + 0.984375
+                }
+            }
+        }
+    }
+
+    pub fn interpolate(&self, a: f32, b: f32, t: f32) -> f32 {
+        lerp(a, b, self.apply(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 12] = [
+        Easing::Linear, Easing::QuadIn, Easing::QuadOut, Easing::QuadInOut,
+        Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut,
+        Easing::SineIn, Easing::SineOut, Easing::SineInOut,
+        Easing::BackOut, Easing::BounceOut
+    ];
+
+    #[test]
+    fn easings_start_at_zero_and_end_at_one() {
+        for easing in EASINGS {
+            assert!(easing.apply(0.0).abs() < 1e-6, "{:?}(0) = {}", easing, easing.apply(0.0));
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{:?}(1) = {}", easing, easing.apply(1.0));
+        }
+    }
+
+    #[test]
+    fn easings_clamp_progress() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(-1.0), easing.apply(0.0));
+            assert_eq!(easing.apply(2.0), easing.apply(1.0));
+        }
+    }
+
+    #[test]
+    fn in_out_easings_are_symmetric() {
+        for easing in [Easing::QuadInOut, Easing::CubicInOut, Easing::SineInOut] {
+            assert!((easing.apply(0.5) - 0.5).abs() < 1e-6);
+            assert!((easing.apply(0.25) + easing.apply(0.75) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn lerp_and_inverse() {
+        assert_eq!(lerp(2.0, 6.0, 0.25), 3.0);
+        assert_eq!(inverse_lerp(2.0, 6.0, 3.0), 0.25);
+        assert_eq!(inverse_lerp(1.0, 1.0, 5.0), 0.0);
+        assert_eq!(remap(5.0, (0.0, 10.0), (100.0, 200.0)), 150.0);
+    }
+
+    #[test]
+    fn lerp_angle_takes_the_short_way() {
+        let angle = lerp_angle(PI - 0.1, -PI + 0.1, 0.5);
+        assert!((angle.abs() - PI).abs() < 1e-5);
+        assert!((lerp_angle(0.1, -0.1, 0.5)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn damp_converges_independently_of_frame_rate() {
+        let mut coarse = 0.0;
+        coarse = damp(coarse, 10.0, 0.9, 1.0);
+
+        let mut fine = 0.0;
+        for _ in 0..10 {
+            fine = damp(fine, 10.0, 0.9, 0.1);
+        }
+
+        assert!((coarse - 9.0_f32).abs() < 1e-4);
+        assert!((coarse - fine).abs() < 1e-4);
+    }
+}
@@ -1 +1,4 @@
-pub mod transform;
\ No newline at end of file
+pub mod transform;
+pub mod transform2d;
+pub mod shapes;
+pub mod interpolation;
\ No newline at end of file
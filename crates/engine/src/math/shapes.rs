@@ -0,0 +1,234 @@
+use cgmath::{InnerSpace, Vector2};
+
+// Axis-aligned rectangle anchored at its bottom-left corner, the convention of
+// the screen-space orthographic projection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    pub fn from_center(center: Vector2<f32>, size: Vector2<f32>) -> Self {
+        Rect::new(center.x - size.x / 2.0, center.y - size.y / 2.0, size.x, size.y)
+    }
+
+    pub fn min(&self) -> Vector2<f32> {
+        Vector2::new(self.x, self.y)
+    }
+
+    pub fn max(&self) -> Vector2<f32> {
+        Vector2::new(self.x + self.width, self.y + self.height)
+    }
+
+    pub fn center(&self) -> Vector2<f32> {
+        Vector2::new(self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    pub fn size(&self) -> Vector2<f32> {
+        Vector2::new(self.width, self.height)
+    }
+
+    pub fn contains(&self, point: Vector2<f32>) -> bool {
+        self.to_aabb().contains(point)
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.to_aabb().intersects(&other.to_aabb())
+    }
+
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        self.to_aabb().intersection(&other.to_aabb()).map(|aabb| aabb.to_rect())
+    }
+
+    pub fn to_aabb(&self) -> Aabb {
+        Aabb::new(self.min(), self.max())
+    }
+}
+
+// Axis-aligned bounding box given by its corners, handier than `Rect` for
+// merging and overlap tests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>
+}
+
+impl Aabb {
+    // Corners may be given in any order.
+    pub fn new(a: Vector2<f32>, b: Vector2<f32>) -> Self {
+        Aabb {
+            min: Vector2::new(a.x.min(b.x), a.y.min(b.y)),
+            max: Vector2::new(a.x.max(b.x), a.y.max(b.y))
+        }
+    }
+
+    pub fn from_points(points: &[Vector2<f32>]) -> Option<Self> {
+        let (first, rest) = points.split_first()?;
+        Some(rest.iter().fold(Aabb::new(*first, *first), |aabb, point| aabb.expanded_to(*point)))
+    }
+
+    pub fn center(&self) -> Vector2<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn half_extents(&self) -> Vector2<f32> {
+        (self.max - self.min) / 2.0
+    }
+
+    pub fn contains(&self, point: Vector2<f32>) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x && point.y >= self.min.y && point.y <= self.max.y
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x
+            && self.min.y <= other.max.y && self.max.y >= other.min.y
+    }
+
+    pub fn intersection(&self, other: &Aabb) -> Option<Aabb> {
+        if !self.intersects(other) {
+            return None;
+        }
+
+        Some(Aabb {
+            min: Vector2::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y)),
+            max: Vector2::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y))
+        })
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector2::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Vector2::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y))
+        }
+    }
+
+    pub fn expanded_to(&self, point: Vector2<f32>) -> Aabb {
+        self.union(&Aabb { min: point, max: point })
+    }
+
+    pub fn closest_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        Vector2::new(point.x.clamp(self.min.x, self.max.x), point.y.clamp(self.min.y, self.max.y))
+    }
+
+    pub fn to_rect(&self) -> Rect {
+        Rect::new(self.min.x, self.min.y, self.max.x - self.min.x, self.max.y - self.min.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    pub center: Vector2<f32>,
+    pub radius: f32
+}
+
+impl Circle {
+    pub fn new(center: Vector2<f32>, radius: f32) -> Self {
+        Circle { center, radius }
+    }
+
+    pub fn contains(&self, point: Vector2<f32>) -> bool {
+        (point - self.center).magnitude2() <= self.radius * self.radius
+    }
+
+    pub fn intersects(&self, other: &Circle) -> bool {
+        let radii = self.radius + other.radius;
+        (other.center - self.center).magnitude2() <= radii * radii
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.contains(aabb.closest_point(self.center))
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        let extents = Vector2::new(self.radius, self.radius);
+        Aabb { min: self.center - extents, max: self.center + extents }
+    }
+}
+
+// Minimum translation that pushes `a` out of `b`, along the axis of least overlap.
+pub fn aabb_penetration(a: &Aabb, b: &Aabb) -> Option<Vector2<f32>> {
+    let overlap = a.intersection(b)?;
+    let size = overlap.max - overlap.min;
+    let direction = a.center() - b.center();
+
+    if size.x < size.y {
+        Some(Vector2::new(size.x.copysign(direction.x), 0.0))
+    } else {
+        Some(Vector2::new(0.0, size.y.copysign(direction.y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rect_intersection() {
+        let a = Rect::new(0.0, 0.0, 4.0, 4.0);
+        let b = Rect::new(2.0, 3.0, 4.0, 4.0);
+        let far = Rect::new(10.0, 0.0, 1.0, 1.0);
+
+        assert!(a.intersects(&b));
+        assert_eq!(a.intersection(&b), Some(Rect::new(2.0, 3.0, 2.0, 1.0)));
+        assert!(!a.intersects(&far));
+        assert_eq!(a.intersection(&far), None);
+
+        // Touching edges count as intersecting
+        assert!(a.intersects(&Rect::new(4.0, 0.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn rect_contains_its_edges() {
+        let rect = Rect::from_center(Vector2::new(0.0, 0.0), Vector2::new(2.0, 2.0));
+        assert!(rect.contains(Vector2::new(1.0, -1.0)));
+        assert!(!rect.contains(Vector2::new(1.01, 0.0)));
+    }
+
+    #[test]
+    fn aabb_from_points_and_union() {
+        let aabb = Aabb::from_points(&[Vector2::new(1.0, 5.0), Vector2::new(-2.0, 0.0), Vector2::new(3.0, 2.0)]).unwrap();
+        assert_eq!(aabb, Aabb::new(Vector2::new(3.0, 5.0), Vector2::new(-2.0, 0.0)));
+        assert_eq!(Aabb::from_points(&[]), None);
+
+        let other = Aabb::new(Vector2::new(10.0, 10.0), Vector2::new(11.0, 11.0));
+        assert_eq!(aabb.union(&other), Aabb::new(Vector2::new(-2.0, 0.0), Vector2::new(11.0, 11.0)));
+    }
+
+    #[test]
+    fn circle_intersections() {
+        let circle = Circle::new(Vector2::new(0.0, 0.0), 1.0);
+
+        assert!(circle.intersects(&Circle::new(Vector2::new(2.0, 0.0), 1.0)));
+        assert!(!circle.intersects(&Circle::new(Vector2::new(2.1, 0.0), 1.0)));
+
+        let aabb = Aabb::new(Vector2::new(0.5, 0.5), Vector2::new(2.0, 2.0));
+        assert!(circle.intersects_aabb(&aabb));
+        // Closest corner of the box is out of reach
+        let corner = Aabb::new(Vector2::new(0.8, 0.8), Vector2::new(2.0, 2.0));
+        assert!(!circle.intersects_aabb(&corner));
+        // Box containing the whole circle
+        let around = Aabb::new(Vector2::new(-5.0, -5.0), Vector2::new(5.0, 5.0));
+        assert!(circle.intersects_aabb(&around));
+    }
+
+    #[test]
+    fn penetration_pushes_along_least_overlap() {
+        let wall = Aabb::new(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0));
+
+        let from_left = Aabb::new(Vector2::new(-1.0, 4.0), Vector2::new(1.0, 6.0));
+        assert_eq!(aabb_penetration(&from_left, &wall), Some(Vector2::new(-1.0, 0.0)));
+
+        let from_top = Aabb::new(Vector2::new(4.0, 9.5), Vector2::new(6.0, 11.5));
+        assert_eq!(aabb_penetration(&from_top, &wall), Some(Vector2::new(0.0, 0.5)));
+
+        let apart = Aabb::new(Vector2::new(20.0, 20.0), Vector2::new(21.0, 21.0));
+        assert_eq!(aabb_penetration(&apart, &wall), None);
+    }
+}
@@ -1,17 +1,20 @@
-use cgmath::{Matrix4, Vector3, Euler, Deg, prelude::*};
+use cgmath::{Matrix3, Matrix4, Vector3, Quaternion, Euler, Deg, Rad, prelude::*};
+
+use crate::math::interpolation::{lerp_vector, slerp};
 
 #[derive(Clone)]
 pub struct Transform {
     // Local space information
     position: Vector3<f32>,
-    euler_rotation: Euler<Deg<f32>>,
+    rotation: Quaternion<f32>,
     scale: Vector3<f32>,
 
     // Global space information concatenate in matrix
     model_matrix: Matrix4<f32>,
 
-    // Dirty flag
-    is_dirty: bool
+    // Global matrix of the parent as of the last propagation, used to solve
+    // world-space setters and to answer global getters without waiting for it
+    parent_matrix: Matrix4<f32>
 }
 
 #[allow(dead_code)]
@@ -19,45 +22,123 @@ impl Transform {
     pub fn new() -> Self {
         Transform {
             position: Vector3::new(0.0, 0.0, 0.0),
-            euler_rotation: Euler::new(Deg(0.0), Deg(0.0), Deg(0.0)),
+            rotation: Quaternion::one(),
             scale: Vector3::new(1.0, 1.0, 1.0),
             model_matrix: Matrix4::identity(),
-            is_dirty: true
+            parent_matrix: Matrix4::identity()
         }
     }
 
     fn get_local_model_matrix(&self) -> Matrix4<f32> {
-        let rotation_matrix = Matrix4::from(self.euler_rotation);
+        let rotation_matrix = Matrix4::from(self.rotation);
         Matrix4::from_translation(self.position) * rotation_matrix * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
     }
 
+    // Global matrix built from the current local values, so it is never stale
+    // with respect to this transform's own setters.
+    pub fn get_global_matrix(&self) -> Matrix4<f32> {
+        self.parent_matrix * self.get_local_model_matrix()
+    }
+
     pub fn compute_model_matrix(&mut self) {
+        self.parent_matrix = Matrix4::identity();
         self.model_matrix = self.get_local_model_matrix();
-        self.is_dirty = false;
     }
 
     pub fn compute_model_matrix_with_parent(&mut self, parent_global_model_matrix: &Matrix4<f32>) {
+        self.parent_matrix = *parent_global_model_matrix;
         self.model_matrix = parent_global_model_matrix * self.get_local_model_matrix();
-        self.is_dirty = false;
+    }
+
+    // Model matrix with the local values blended from `previous` towards the
+    // current ones. `alpha` goes from 0 (previous) to 1 (current).
+    pub fn compute_interpolated_model_matrix(&mut self, parent_global_model_matrix: Option<&Matrix4<f32>>, previous: &Transform, alpha: f32) {
+        self.parent_matrix = parent_global_model_matrix.copied().unwrap_or(Matrix4::identity());
+
+        let position = lerp_vector(previous.position, self.position, alpha);
+        let rotation = slerp(previous.rotation, self.rotation, alpha);
+        let scale = lerp_vector(previous.scale, self.scale, alpha);
+
+        let local_matrix = Matrix4::from_translation(position) * Matrix4::from(rotation) * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
+        self.model_matrix = self.parent_matrix * local_matrix;
     }
 
     pub fn set_local_position(&mut self, new_position: Vector3<f32>) {
         self.position = new_position;
-        self.is_dirty = true;
     }
 
+    // Euler angles in degrees.
     pub fn set_local_rotation(&mut self, new_rotation: Vector3<f32>) {
-        self.euler_rotation = Euler::new(Deg(new_rotation.x), Deg(new_rotation.y), Deg(new_rotation.z));
-        self.is_dirty = true;
+        self.rotation = Quaternion::from(Euler::new(Deg(new_rotation.x), Deg(new_rotation.y), Deg(new_rotation.z)));
+    }
+
+    pub fn set_local_rotation_quat(&mut self, new_rotation: Quaternion<f32>) {
+        self.rotation = new_rotation.normalize();
     }
 
     pub fn set_local_scale(&mut self, new_scale: Vector3<f32>) {
         self.scale = new_scale;
-        self.is_dirty = true;
+    }
+
+    pub fn set_global_position(&mut self, new_position: Vector3<f32>) {
+        match self.parent_matrix.invert() {
+            Some(parent_inverse) => self.position = (parent_inverse * new_position.extend(1.0)).truncate(),
+            None => eprintln!("Cannot set the global position of a transform whose parent has a zero scale")
+        }
+    }
+
+    pub fn set_global_rotation(&mut self, new_rotation: Quaternion<f32>) {
+        let parent_rotation = rotation_of(&self.parent_matrix);
+        self.rotation = (parent_rotation.invert() * new_rotation).normalize();
+    }
+
+    // Shear introduced by non-uniformly scaled, rotated parents is not representable
+    // and is ignored.
+    pub fn set_global_scale(&mut self, new_scale: Vector3<f32>) {
+        let parent_scale = scale_of(&self.parent_matrix);
+        if parent_scale.x == 0.0 || parent_scale.y == 0.0 || parent_scale.z == 0.0 {
+            eprintln!("Cannot set the global scale of a transform whose parent has a zero scale");
+            return;
+        }
+        self.scale = Vector3::new(new_scale.x / parent_scale.x, new_scale.y / parent_scale.y, new_scale.z / parent_scale.z);
+    }
+
+    // Turns the transform so that its forward axis (-Z) points at `target`, in world space.
+    pub fn look_at(&mut self, target: Vector3<f32>, up: Vector3<f32>) {
+        let direction = target - self.get_global_position();
+        if direction.magnitude2() == 0.0 {
+            return;
+        }
+
+        let backward = -direction.normalize();
+        let right = up.cross(backward);
+        if right.magnitude2() == 0.0 {
+            eprintln!("Cannot look at a target aligned with the up vector");
+            return;
+        }
+        let right = right.normalize();
+        let up = backward.cross(right);
+
+        self.set_global_rotation(Quaternion::from(Matrix3::from_cols(right, up, backward)));
+    }
+
+    // Orbits around `point` (world space) by `angle` degrees, turning the transform as it goes.
+    pub fn rotate_around(&mut self, point: Vector3<f32>, axis: Vector3<f32>, angle: f32) {
+        let rotation = Quaternion::from_axis_angle(axis.normalize(), Deg(angle));
+
+        let position = self.get_global_position();
+        let global_rotation = self.get_global_rotation();
+
+        self.set_global_position(point + rotation.rotate_vector(position - point));
+        self.set_global_rotation(rotation * global_rotation);
     }
 
     pub fn get_global_position(&self) -> Vector3<f32> {
-        self.model_matrix.w.truncate()
+        self.get_global_matrix().w.truncate()
+    }
+
+    pub fn get_global_rotation(&self) -> Quaternion<f32> {
+        rotation_of(&self.parent_matrix) * self.rotation
     }
 
     pub fn get_local_position(&self) -> &Vector3<f32> {
@@ -68,8 +149,14 @@ impl Transform {
         &mut self.position
     }
 
+    // Euler angles in degrees.
     pub fn get_local_rotation(&self) -> Vector3<f32> {
-        Vector3::new(self.euler_rotation.x.0, self.euler_rotation.y.0, self.euler_rotation.z.0)
+        let euler: Euler<Rad<f32>> = Euler::from(self.rotation);
+        Vector3::new(Deg::from(euler.x).0, Deg::from(euler.y).0, Deg::from(euler.z).0)
+    }
+
+    pub fn get_local_rotation_quat(&self) -> Quaternion<f32> {
+        self.rotation
     }
 
     pub fn get_local_scale(&self) -> &Vector3<f32> {
@@ -81,15 +168,15 @@ impl Transform {
     }
 
     pub fn get_right(&self) -> Vector3<f32> {
-        self.model_matrix.x.truncate().normalize()
+        self.get_global_matrix().x.truncate().normalize()
     }
 
     pub fn get_up(&self) -> Vector3<f32> {
-        self.model_matrix.y.truncate().normalize()
+        self.get_global_matrix().y.truncate().normalize()
     }
 
     pub fn get_backward(&self) -> Vector3<f32> {
-        self.model_matrix.z.truncate().normalize()
+        self.get_global_matrix().z.truncate().normalize()
     }
 
     pub fn get_forward(&self) -> Vector3<f32> {
@@ -97,10 +184,147 @@ impl Transform {
     }
 
     pub fn get_global_scale(&self) -> Vector3<f32> {
-        Vector3::new(self.get_right().magnitude(), self.get_up().magnitude(), self.get_backward().magnitude())
+        scale_of(&self.get_global_matrix())
+    }
+}
+
+// Length of each basis axis of the matrix. A mirrored matrix (negative
+// determinant) gets a negative x, so that what remains is a rotation.
+fn scale_of(matrix: &Matrix4<f32>) -> Vector3<f32> {
+    let (x, y, z) = (matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+    let mut scale = Vector3::new(x.magnitude(), y.magnitude(), z.magnitude());
+    if Matrix3::from_cols(x, y, z).determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+    scale
+}
+
+// Rotation part of the matrix, with the scale divided out of each axis.
+fn rotation_of(matrix: &Matrix4<f32>) -> Quaternion<f32> {
+    let scale = scale_of(matrix);
+    if scale.x == 0.0 || scale.y == 0.0 || scale.z == 0.0 {
+        return Quaternion::one();
+    }
+
+    Quaternion::from(Matrix3::from_cols(
+        matrix.x.truncate() / scale.x,
+        matrix.y.truncate() / scale.y,
+        matrix.z.truncate() / scale.z
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_vector(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).magnitude() < 1e-4, "{:?} != {:?}", actual, expected);
+    }
+
+    // q and -q are the same rotation
+    fn assert_rotation(actual: Quaternion<f32>, expected: Quaternion<f32>) {
+        assert!(actual.dot(expected).abs() > 1.0 - 1e-5, "{:?} != {:?}", actual, expected);
     }
 
-    pub fn is_dirty(&self) -> bool {
-        self.is_dirty
+    fn rotation_z(angle: f32) -> Quaternion<f32> {
+        Quaternion::from_axis_angle(Vector3::unit_z(), Deg(angle))
     }
-}
\ No newline at end of file
+
+    fn parent(position: Vector3<f32>, rotation: f32, scale: Vector3<f32>) -> Transform {
+        let mut parent = Transform::new();
+        parent.set_local_position(position);
+        parent.set_local_rotation_quat(rotation_z(rotation));
+        parent.set_local_scale(scale);
+        parent.compute_model_matrix();
+        parent
+    }
+
+    fn child_of(parent: &Transform) -> Transform {
+        let mut child = Transform::new();
+        child.set_local_position(Vector3::new(1.0, 2.0, 0.0));
+        child.set_local_rotation_quat(rotation_z(30.0));
+        child.compute_model_matrix_with_parent(&parent.get_global_matrix());
+        child
+    }
+
+    #[test]
+    fn global_scale_is_not_normalized() {
+        let mut transform = Transform::new();
+        transform.set_local_scale(Vector3::new(2.0, 3.0, 4.0));
+        transform.set_local_rotation(Vector3::new(0.0, 0.0, 45.0));
+        assert_vector(transform.get_global_scale(), Vector3::new(2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn global_setters_round_trip_under_a_parent() {
+        let parent = parent(Vector3::new(10.0, 0.0, 0.0), 90.0, Vector3::new(2.0, 2.0, 2.0));
+        let mut child = child_of(&parent);
+
+        // Local (1, 2) turned a quarter and doubled
+        assert_vector(child.get_global_position(), Vector3::new(6.0, 2.0, 0.0));
+
+        child.set_global_position(Vector3::new(-3.0, 5.0, 1.0));
+        assert_vector(child.get_global_position(), Vector3::new(-3.0, 5.0, 1.0));
+
+        child.set_global_rotation(rotation_z(10.0));
+        assert_rotation(child.get_global_rotation(), rotation_z(10.0));
+        assert_rotation(child.get_local_rotation_quat(), rotation_z(-80.0));
+
+        child.set_global_scale(Vector3::new(1.0, 3.0, 4.0));
+        assert_vector(child.get_global_scale(), Vector3::new(1.0, 3.0, 4.0));
+        assert_vector(*child.get_local_scale(), Vector3::new(0.5, 1.5, 2.0));
+
+        // Still where it was put once the system propagates
+        child.compute_model_matrix_with_parent(&parent.get_global_matrix());
+        assert_vector(child.get_model_matrix().w.truncate(), Vector3::new(-3.0, 5.0, 1.0));
+    }
+
+    #[test]
+    fn mirrored_parent_keeps_a_rotation() {
+        let parent = parent(Vector3::new(0.0, 0.0, 0.0), 90.0, Vector3::new(-1.0, 1.0, 1.0));
+        assert_vector(parent.get_global_scale(), Vector3::new(-1.0, 1.0, 1.0));
+        assert_rotation(rotation_of(&parent.get_global_matrix()), rotation_z(90.0));
+
+        let mut child = child_of(&parent);
+        child.set_global_rotation(rotation_z(45.0));
+        assert_rotation(child.get_global_rotation(), rotation_z(45.0));
+
+        // Mirrored twice: the child is not mirrored any more
+        child.set_global_scale(Vector3::new(2.0, 2.0, 2.0));
+        assert_vector(*child.get_local_scale(), Vector3::new(-2.0, 2.0, 2.0));
+        assert_vector(child.get_global_scale(), Vector3::new(2.0, 2.0, 2.0));
+
+        child.set_global_position(Vector3::new(4.0, -1.0, 0.0));
+        assert_vector(child.get_global_position(), Vector3::new(4.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn look_at_points_forward_at_the_target() {
+        let parent = parent(Vector3::new(0.0, 5.0, 0.0), 90.0, Vector3::new(1.0, 1.0, 1.0));
+        let mut child = child_of(&parent);
+        let target = Vector3::new(10.0, 3.0, 0.0);
+
+        child.look_at(target, Vector3::unit_z());
+        let direction = (target - child.get_global_position()).normalize();
+        assert_vector(child.get_forward(), direction);
+        assert_vector(child.get_up(), Vector3::unit_z());
+
+        // Nothing sensible to do along the up vector
+        let rotation = child.get_local_rotation_quat();
+        child.look_at(child.get_global_position() + Vector3::unit_z(), Vector3::unit_z());
+        assert_rotation(child.get_local_rotation_quat(), rotation);
+    }
+
+    #[test]
+    fn rotate_around_orbits_and_turns() {
+        let parent = parent(Vector3::new(3.0, 0.0, 0.0), 0.0, Vector3::new(1.0, 1.0, 1.0));
+        let mut child = Transform::new();
+        child.set_local_position(Vector3::new(1.0, 0.0, 0.0));
+        child.compute_model_matrix_with_parent(&parent.get_global_matrix());
+
+        child.rotate_around(Vector3::new(2.0, 0.0, 0.0), Vector3::unit_z(), 90.0);
+        assert_vector(child.get_global_position(), Vector3::new(2.0, 2.0, 0.0));
+        assert_rotation(child.get_global_rotation(), rotation_z(90.0));
+        assert_vector(child.get_right(), Vector3::unit_y());
+    }
+}
@@ -0,0 +1,281 @@
+use std::ops::Mul;
+
+use cgmath::{Matrix4, Vector2, Vector3, Vector4};
+
+// 2D affine matrix stored as its two non-constant rows:
+// | a  c  tx |
+// | b  d  ty |
+// | 0  0  1  |
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine2 {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub tx: f32,
+    pub ty: f32
+}
+
+impl Affine2 {
+    pub const IDENTITY: Affine2 = Affine2 { a: 1.0, b: 0.0, c: 0.0, d: 1.0, tx: 0.0, ty: 0.0 };
+
+    pub fn from_translation(translation: Vector2<f32>) -> Self {
+        Affine2 { tx: translation.x, ty: translation.y, ..Self::IDENTITY }
+    }
+
+    // `angle` in radians, counter-clockwise.
+    pub fn from_angle(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Affine2 { a: cos, b: sin, c: -sin, d: cos, tx: 0.0, ty: 0.0 }
+    }
+
+    pub fn from_scale(scale: Vector2<f32>) -> Self {
+        Affine2 { a: scale.x, d: scale.y, ..Self::IDENTITY }
+    }
+
+    // Translation * rotation * scale, without going through three multiplications.
+    pub fn from_trs(translation: Vector2<f32>, angle: f32, scale: Vector2<f32>) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Affine2 {
+            a: cos * scale.x,
+            b: sin * scale.x,
+            c: -sin * scale.y,
+            d: cos * scale.y,
+            tx: translation.x,
+            ty: translation.y
+        }
+    }
+
+    pub fn transform_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        Vector2::new(
+            self.a * point.x + self.c * point.y + self.tx,
+            self.b * point.x + self.d * point.y + self.ty
+        )
+    }
+
+    // Ignores the translation.
+    pub fn transform_vector(&self, vector: Vector2<f32>) -> Vector2<f32> {
+        Vector2::new(
+            self.a * vector.x + self.c * vector.y,
+            self.b * vector.x + self.d * vector.y
+        )
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    pub fn inverse(&self) -> Option<Affine2> {
+        let determinant = self.determinant();
+        if determinant.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let inv = 1.0 / determinant;
+        let a = self.d * inv;
+        let b = -self.b * inv;
+        let c = -self.c * inv;
+        let d = self.a * inv;
+
+        Some(Affine2 {
+            a,
+            b,
+            c,
+            d,
+            tx: -(a * self.tx + c * self.ty),
+            ty: -(b * self.tx + d * self.ty)
+        })
+    }
+
+    pub fn translation(&self) -> Vector2<f32> {
+        Vector2::new(self.tx, self.ty)
+    }
+
+    // Expands to the 4x4 matrix the shaders expect, placing the result at depth `z`.
+    pub fn to_matrix4(&self, z: f32) -> Matrix4<f32> {
+        Matrix4::from_cols(
+            Vector4::new(self.a, self.b, 0.0, 0.0),
+            Vector4::new(self.c, self.d, 0.0, 0.0),
+            Vector4::new(0.0, 0.0, 1.0, 0.0),
+            Vector4::new(self.tx, self.ty, z, 1.0)
+        )
+    }
+}
+
+impl Mul for Affine2 {
+    type Output = Affine2;
+
+    fn mul(self, rhs: Affine2) -> Affine2 {
+        Affine2 {
+            a: self.a * rhs.a + self.c * rhs.b,
+            b: self.b * rhs.a + self.d * rhs.b,
+            c: self.a * rhs.c + self.c * rhs.d,
+            d: self.b * rhs.c + self.d * rhs.d,
+            tx: self.a * rhs.tx + self.c * rhs.ty + self.tx,
+            ty: self.b * rhs.tx + self.d * rhs.ty + self.ty
+        }
+    }
+}
+
+// Position, angle and scale in the plane, plus the layer depth used to order
+// sprites. Lighter than `Transform` for code that never leaves 2D.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform2D {
+    pub position: Vector2<f32>,
+    // Radians, counter-clockwise.
+    pub angle: f32,
+    pub scale: Vector2<f32>,
+    pub z: f32
+}
+
+// The identity transform.
+impl Default for Transform2D {
+    fn default() -> Self {
+        Transform2D::new()
+    }
+}
+
+#[allow(dead_code)]
+impl Transform2D {
+    pub fn new() -> Self {
+        Transform2D {
+            position: Vector2::new(0.0, 0.0),
+            angle: 0.0,
+            scale: Vector2::new(1.0, 1.0),
+            z: 0.0
+        }
+    }
+
+    pub fn from_position(position: Vector2<f32>) -> Self {
+        Transform2D { position, ..Self::new() }
+    }
+
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        self.angle = angle;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vector2<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_z(mut self, z: f32) -> Self {
+        self.z = z;
+        self
+    }
+
+    pub fn translate(&mut self, offset: Vector2<f32>) {
+        self.position += offset;
+    }
+
+    pub fn rotate(&mut self, angle: f32) {
+        self.angle += angle;
+    }
+
+    pub fn to_affine(&self) -> Affine2 {
+        Affine2::from_trs(self.position, self.angle, self.scale)
+    }
+
+    pub fn to_matrix4(&self) -> Matrix4<f32> {
+        self.to_affine().to_matrix4(self.z)
+    }
+
+    pub fn transform_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        self.to_affine().transform_point(point)
+    }
+
+    pub fn right(&self) -> Vector2<f32> {
+        let (sin, cos) = self.angle.sin_cos();
+        Vector2::new(cos, sin)
+    }
+
+    pub fn up(&self) -> Vector2<f32> {
+        let (sin, cos) = self.angle.sin_cos();
+        Vector2::new(-sin, cos)
+    }
+
+    pub fn position3(&self) -> Vector3<f32> {
+        self.position.extend(self.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    fn assert_close(actual: Affine2, expected: Affine2) {
+        let pairs = [
+            (actual.a, expected.a), (actual.b, expected.b), (actual.c, expected.c),
+            (actual.d, expected.d), (actual.tx, expected.tx), (actual.ty, expected.ty)
+        ];
+        for (a, e) in pairs {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn mul_applies_right_hand_side_first() {
+        let translate = Affine2::from_translation(Vector2::new(10.0, 0.0));
+        let rotate = Affine2::from_angle(FRAC_PI_2);
+
+        // Rotated first, then moved
+        let point = (translate * rotate).transform_point(Vector2::new(1.0, 0.0));
+        assert!((point.x - 10.0).abs() < 1e-5 && (point.y - 1.0).abs() < 1e-5);
+
+        // Moved first, then rotated around the origin
+        let point = (rotate * translate).transform_point(Vector2::new(1.0, 0.0));
+        assert!(point.x.abs() < 1e-5 && (point.y - 11.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn from_trs_matches_products() {
+        let translation = Vector2::new(3.0, -2.0);
+        let scale = Vector2::new(2.0, 0.5);
+        let product = Affine2::from_translation(translation) * Affine2::from_angle(0.7) * Affine2::from_scale(scale);
+        assert_close(Affine2::from_trs(translation, 0.7, scale), product);
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Affine2::from_trs(Vector2::new(5.0, 7.0), 1.2, Vector2::new(3.0, -0.5));
+        let inverse = transform.inverse().unwrap();
+
+        assert_close(transform * inverse, Affine2::IDENTITY);
+        assert_close(inverse * transform, Affine2::IDENTITY);
+
+        let point = Vector2::new(-4.0, 2.5);
+        let back = inverse.transform_point(transform.transform_point(point));
+        assert!((back - point).x.abs() < 1e-4 && (back - point).y.abs() < 1e-4);
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert_eq!(Affine2::from_scale(Vector2::new(0.0, 1.0)).inverse(), None);
+    }
+
+    #[test]
+    fn transform_vector_ignores_translation() {
+        let transform = Affine2::from_trs(Vector2::new(100.0, 100.0), 0.0, Vector2::new(2.0, 3.0));
+        assert_eq!(transform.transform_vector(Vector2::new(1.0, 1.0)), Vector2::new(2.0, 3.0));
+    }
+
+    #[test]
+    fn matrix4_places_point_at_depth() {
+        let transform = Transform2D::from_position(Vector2::new(4.0, 5.0)).with_angle(0.3).with_z(2.0);
+        let point = transform.to_matrix4() * Vector4::new(1.0, 0.0, 0.0, 1.0);
+        let expected = transform.transform_point(Vector2::new(1.0, 0.0));
+
+        assert!((point.x - expected.x).abs() < 1e-5 && (point.y - expected.y).abs() < 1e-5);
+        assert_eq!(point.z, 2.0);
+    }
+
+    #[test]
+    fn default_is_the_identity() {
+        let transform = Transform2D::default();
+        assert_eq!(transform.to_affine(), Affine2::IDENTITY);
+        assert_eq!(transform.z, 0.0);
+    }
+}
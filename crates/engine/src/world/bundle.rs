@@ -0,0 +1,140 @@
+use crate::world::components::Component;
+use crate::world::entity::Entity;
+use crate::world::world::World;
+
+pub use engine_macros::Bundle;
+
+// A group of components inserted together. Every component is a bundle on its
+// own, tuples of bundles are bundles, and structs get it from `#[derive(Bundle)]`
+// as long as all their fields are bundles.
+pub trait Bundle: Send + Sync + 'static {
+    fn insert_into(self, world: &mut World, entity: Entity);
+}
+
+impl<T: Component + 'static + Send + Sync> Bundle for T {
+    fn insert_into(self, world: &mut World, entity: Entity) {
+        world.add_component(entity, self);
+    }
+}
+
+macro_rules! tuple_bundle {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case, unused_variables)]
+        impl<$($name: Bundle),*> Bundle for ($($name,)*) {
+            fn insert_into(self, world: &mut World, entity: Entity) {
+                let ($($name,)*) = self;
+                $($name.insert_into(world, entity);)*
+            }
+        }
+    };
+}
+
+tuple_bundle!();
+tuple_bundle!(A);
+tuple_bundle!(A, B);
+tuple_bundle!(A, B, C);
+tuple_bundle!(A, B, C, D);
+tuple_bundle!(A, B, C, D, E);
+tuple_bundle!(A, B, C, D, E, F);
+tuple_bundle!(A, B, C, D, E, F, G);
+tuple_bundle!(A, B, C, D, E, F, G, H);
+tuple_bundle!(A, B, C, D, E, F, G, H, I);
+tuple_bundle!(A, B, C, D, E, F, G, H, I, J);
+tuple_bundle!(A, B, C, D, E, F, G, H, I, J, K);
+tuple_bundle!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+impl World {
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let entity = self.new_entity();
+        bundle.insert_into(self, entity);
+        entity
+    }
+
+    pub fn insert_bundle<B: Bundle>(&mut self, entity: Entity, bundle: B) {
+        if !self.is_alive(entity) {
+            eprintln!("Cannot insert a bundle on {:?}: the entity is not alive", entity);
+            return;
+        }
+
+        bundle.insert_into(self, entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+    impl Component for Name {}
+
+    #[derive(Debug, PartialEq)]
+    struct Speed(f32);
+    impl Component for Speed {}
+
+    #[derive(Bundle)]
+    struct Living {
+        health: Health,
+        name: Name
+    }
+
+    #[derive(Bundle)]
+    struct Runner(Living, Speed);
+
+    #[test]
+    fn tuple_bundle_inserts_every_component() {
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.insert_bundle(entity, (Health(3), (Name("nested"), Speed(1.5))));
+
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health(3)));
+        assert_eq!(world.get_component::<Name>(entity), Some(&Name("nested")));
+        assert_eq!(world.get_component::<Speed>(entity), Some(&Speed(1.5)));
+    }
+
+    #[test]
+    fn derived_bundle_nests_other_bundles() {
+        let mut world = World::new();
+        let entity = world.spawn(Runner(Living { health: Health(10), name: Name("runner") }, Speed(4.0)));
+
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health(10)));
+        assert_eq!(world.get_component::<Name>(entity), Some(&Name("runner")));
+        assert_eq!(world.get_component::<Speed>(entity), Some(&Speed(4.0)));
+    }
+
+    #[test]
+    fn spawn_returns_a_live_entity() {
+        let mut world = World::new();
+        let first = world.spawn(());
+        let second = world.spawn((Health(1), Name("second")));
+
+        assert!(world.is_alive(first));
+        assert!(world.is_alive(second));
+        assert_ne!(first, second);
+        assert_eq!(world.entity_count(), 2);
+        assert_eq!(world.get_component::<Health>(second), Some(&Health(1)));
+        assert_eq!(world.get_component::<Name>(second), Some(&Name("second")));
+        assert!(world.get_component::<Health>(first).is_none());
+    }
+
+    #[test]
+    fn insert_bundle_on_dead_entity_does_nothing() {
+        let mut world = World::new();
+        let entity = world.spawn(Health(1));
+        world.remove_entity(entity);
+
+        world.insert_bundle(entity, (Health(2), Name("ghost")));
+        assert!(!world.is_alive(entity));
+        assert!(world.get_components::<Health>().is_some_and(|health| health.is_empty()));
+        assert!(world.get_components::<Name>().is_none_or(|names| names.is_empty()));
+
+        // The reused index does not inherit anything
+        let reused = world.spawn(());
+        assert_eq!(reused.index(), entity.index());
+        assert!(world.get_component::<Health>(reused).is_none());
+    }
+}
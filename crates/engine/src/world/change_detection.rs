@@ -0,0 +1,193 @@
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use crate::world::entity::Entity;
+
+// World change ticks at which a component was inserted and last mutated.
+// A tick is "new" for a system when it is later than the system's last run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: u64,
+    pub changed: u64
+}
+
+impl ComponentTicks {
+    pub fn new(tick: u64) -> Self {
+        ComponentTicks { added: tick, changed: tick }
+    }
+
+    pub fn is_added(&self, last_run: u64) -> bool {
+        self.added > last_run
+    }
+
+    pub fn is_changed(&self, last_run: u64) -> bool {
+        self.changed > last_run
+    }
+}
+
+// Mutable component handed out by `&mut T` queries. Reading goes through
+// `Deref` untouched; the first `DerefMut` marks the component as changed.
+pub struct Mut<'w, T> {
+    pub(crate) value: &'w mut T,
+    pub(crate) ticks: &'w mut ComponentTicks,
+    pub(crate) last_run: u64,
+    pub(crate) this_run: u64
+}
+
+impl<T> Mut<'_, T> {
+    pub fn is_added(&self) -> bool {
+        self.ticks.is_added(self.last_run)
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.ticks.is_changed(self.last_run)
+    }
+
+    pub fn set_changed(&mut self) {
+        self.ticks.changed = self.this_run;
+    }
+
+    // Mutates without recording a change, e.g. for caches derived from the component itself.
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T> Deref for Mut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Mut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.set_changed();
+        self.value
+    }
+}
+
+// Entities that lost a `T` (or were despawned with one) since the reading
+// system last ran. Removals are kept for two frames, like events.
+pub struct RemovedComponents<'w, T> {
+    pub(crate) removed: Vec<&'w [(Entity, u64)]>,
+    pub(crate) last_run: u64,
+    pub(crate) _marker: PhantomData<fn() -> T>
+}
+
+impl<T> RemovedComponents<'_, T> {
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        let last_run = self.last_run;
+        self.removed.iter()
+            .flat_map(|removed| removed.iter())
+            .filter(move |(_, tick)| *tick > last_run)
+            .map(|(entity, _)| *entity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::components::Component;
+    use crate::world::entity::Entity;
+    use crate::world::query::{Added, Changed};
+    use crate::world::world::World;
+
+    struct Position(i32);
+    impl Component for Position {}
+
+    // Runs `f` the way the schedule runs a system last run at `*last_run`.
+    fn run_system<R>(world: &mut World, last_run: &mut u64, f: impl FnOnce(&mut World) -> R) -> R {
+        *last_run = world.begin_system_run(*last_run);
+        let result = f(world);
+        world.end_system_run();
+        result
+    }
+
+    fn added(world: &mut World) -> Vec<Entity> {
+        world.query_filtered::<Entity, Added<Position>>().collect()
+    }
+
+    fn changed(world: &mut World) -> Vec<Entity> {
+        world.query_filtered::<Entity, Changed<Position>>().collect()
+    }
+
+    #[test]
+    fn added_is_seen_once_per_system() {
+        let mut world = World::new();
+        let mut reader = 0;
+        let mut other_reader = 0;
+
+        let entity = world.new_entity();
+        world.add_component(entity, Position(0));
+
+        assert_eq!(run_system(&mut world, &mut reader, added), vec![entity]);
+        assert_eq!(run_system(&mut world, &mut reader, added), vec![]);
+        // Every system gets to see it once
+        assert_eq!(run_system(&mut world, &mut other_reader, added), vec![entity]);
+    }
+
+    #[test]
+    fn changed_tracks_mutable_access() {
+        let mut world = World::new();
+        let mut writer = 0;
+        let mut reader = 0;
+
+        let moved = world.new_entity();
+        let still = world.new_entity();
+        world.add_component(moved, Position(0));
+        world.add_component(still, Position(0));
+
+        // Insertion counts as a change
+        assert_eq!(run_system(&mut world, &mut reader, changed).len(), 2);
+        assert_eq!(run_system(&mut world, &mut reader, changed), vec![]);
+
+        run_system(&mut world, &mut writer, |world| {
+            for (entity, mut position) in world.query::<(Entity, &mut Position)>() {
+                if entity == moved {
+                    position.0 += 1;
+                } else {
+                    // Reading through `Mut` is not a change
+                    assert_eq!(position.0, 0);
+                }
+            }
+        });
+
+        assert_eq!(run_system(&mut world, &mut reader, changed), vec![moved]);
+        assert_eq!(run_system(&mut world, &mut reader, changed), vec![]);
+
+        // The writer does not see its own change on its next run
+        assert_eq!(run_system(&mut world, &mut writer, changed), vec![]);
+    }
+
+    #[test]
+    fn removed_components_are_reported_until_trimmed() {
+        let mut world = World::new();
+        let mut reader = 0;
+
+        let removed = world.new_entity();
+        let despawned = world.new_entity();
+        world.add_component(removed, Position(0));
+        world.add_component(despawned, Position(0));
+        run_system(&mut world, &mut reader, |_| ());
+
+        world.remove_component::<Position>(removed);
+        world.remove_entity(despawned);
+
+        let seen: Vec<Entity> = run_system(&mut world, &mut reader, |world| world.removed::<Position>().iter().collect());
+        assert_eq!(seen, vec![removed, despawned]);
+        assert!(run_system(&mut world, &mut reader, |world| world.removed::<Position>().is_empty()));
+
+        // A system that did not run in between still sees them, for two frames
+        let mut late_reader = 0;
+        world.update_removed_components();
+        assert!(!run_system(&mut world, &mut late_reader, |world| world.removed::<Position>().is_empty()));
+        world.update_removed_components();
+        late_reader = 0;
+        assert!(run_system(&mut world, &mut late_reader, |world| world.removed::<Position>().is_empty()));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::world::bundle::Bundle;
+use crate::world::components::Component;
+use crate::world::entity::{Entity, EntityReserver};
+use crate::world::world::World;
+
+type EntityCommand = Box<dyn FnOnce(&mut World, Entity) + Send>;
+
+enum Command {
+    Spawn(Entity, Vec<EntityCommand>),
+    Insert(Entity, EntityCommand),
+    Remove(Entity, EntityCommand),
+    Despawn(Entity),
+    Custom(Box<dyn FnOnce(&mut World) + Send>)
+}
+
+// Structural world changes recorded while a system runs and applied once it
+// returns, so systems never spawn or despawn in the middle of a query.
+// Spawned entities get their id right away, reserved from the world.
+pub struct Commands {
+    queue: Vec<Command>,
+    reserver: EntityReserver
+}
+
+impl Commands {
+    pub fn new(world: &World) -> Self {
+        Commands {
+            queue: Vec::new(),
+            reserver: world.entity_reserver()
+        }
+    }
+
+    pub fn spawn(&mut self) -> EntityCommands<'_> {
+        let entity = self.reserver.reserve();
+        self.queue.push(Command::Spawn(entity, Vec::new()));
+        let index = self.queue.len() - 1;
+        EntityCommands { commands: self, index, entity }
+    }
+
+    pub fn spawn_bundle<B: Bundle>(&mut self, bundle: B) -> EntityCommands<'_> {
+        let mut entity_commands = self.spawn();
+        entity_commands.insert(bundle);
+        entity_commands
+    }
+
+    pub fn insert<B: Bundle>(&mut self, entity: Entity, bundle: B) {
+        self.queue.push(Command::Insert(entity, inserter(bundle)));
+    }
+
+    pub fn remove<T: Component + 'static + Send + Sync>(&mut self, entity: Entity) {
+        self.queue.push(Command::Remove(entity, Box::new(|world, entity| {
+            world.remove_component::<T>(entity);
+        })));
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.queue.push(Command::Despawn(entity));
+    }
+
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        self.add(move |world| world.set_parent(child, parent));
+    }
+
+    pub fn remove_parent(&mut self, child: Entity) {
+        self.add(move |world| world.remove_parent(child));
+    }
+
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        self.add(move |world| world.despawn_recursive(entity));
+    }
+
+    pub fn add<F: FnOnce(&mut World) + Send + 'static>(&mut self, command: F) {
+        self.queue.push(Command::Custom(Box::new(command)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn apply(&mut self, world: &mut World) {
+        assert!(self.reserver.is_same(&world.entity_reserver()), "Commands applied to another world than theirs");
+        world.flush_entities();
+
+        for command in self.queue.drain(..) {
+            match command {
+                Command::Spawn(entity, inserters) => {
+                    for insert in inserters {
+                        insert(world, entity);
+                    }
+                }
+                Command::Insert(entity, insert) => insert(world, entity),
+                Command::Remove(entity, remove) => remove(world, entity),
+                Command::Despawn(entity) => world.remove_entity(entity),
+                Command::Custom(command) => command(world)
+            }
+        }
+    }
+}
+
+// Spawns that were never applied give their ids back.
+impl Drop for Commands {
+    fn drop(&mut self) {
+        for command in &self.queue {
+            if let Command::Spawn(entity, _) = command {
+                self.reserver.cancel(*entity);
+            }
+        }
+    }
+}
+
+pub struct EntityCommands<'a> {
+    commands: &'a mut Commands,
+    index: usize,
+    entity: Entity
+}
+
+impl EntityCommands<'_> {
+    // Usable in other commands right away, alive once they are applied.
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn insert<B: Bundle>(&mut self, bundle: B) -> &mut Self {
+        if let Command::Spawn(_, inserters) = &mut self.commands.queue[self.index] {
+            inserters.push(inserter(bundle));
+        }
+        self
+    }
+}
+
+fn inserter<B: Bundle>(bundle: B) -> EntityCommand {
+    Box::new(move |world, entity| world.insert_bundle(entity, bundle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::components::Parent;
+
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[test]
+    fn spawned_id_is_usable_before_apply() {
+        let mut world = World::new();
+        let parent = world.new_entity();
+        let mut commands = Commands::new(&world);
+
+        let child = commands.spawn_bundle(Health(10)).id();
+        assert!(!world.is_alive(child));
+
+        commands.set_parent(child, parent);
+        commands.insert(child, Health(20));
+        commands.apply(&mut world);
+
+        assert!(world.is_alive(child));
+        assert_eq!(world.get_component::<Health>(child).map(|health| health.0), Some(20));
+        assert_eq!(world.get_component::<Parent>(child).map(|parent| parent.get()), Some(parent));
+    }
+
+    #[test]
+    fn dropped_commands_do_not_leak_entities() {
+        let mut world = World::new();
+        let mut commands = Commands::new(&world);
+        let spawned = commands.spawn_bundle(Health(10)).id();
+        drop(commands);
+
+        world.flush_entities();
+        assert!(!world.is_alive(spawned));
+        assert_eq!(world.entity_count(), 0);
+        assert_eq!(world.new_entity().index(), spawned.index());
+    }
+
+    #[test]
+    fn commands_dropped_after_a_flush_despawn_their_entities() {
+        let mut world = World::new();
+        let mut dropped = Commands::new(&world);
+        let spawned = dropped.spawn().id();
+
+        // Applying other commands flushes every reserved id
+        let mut applied = Commands::new(&world);
+        let kept = applied.spawn().id();
+        applied.apply(&mut world);
+        assert!(world.is_alive(spawned));
+
+        drop(dropped);
+        world.flush_entities();
+        assert!(!world.is_alive(spawned));
+        assert!(world.is_alive(kept));
+        assert_eq!(world.entity_count(), 1);
+    }
+
+    #[test]
+    fn spawned_entity_can_be_despawned_before_apply() {
+        let mut world = World::new();
+        let mut commands = Commands::new(&world);
+
+        let entity = commands.spawn().insert(Health(1)).id();
+        commands.despawn(entity);
+        commands.apply(&mut world);
+
+        assert!(!world.is_alive(entity));
+        assert_eq!(world.entity_count(), 0);
+
+        // The index is recycled by the next spawn, with a new generation
+        let next = commands.spawn().id();
+        commands.apply(&mut world);
+        assert_eq!(next.index(), entity.index());
+        assert!(world.is_alive(next));
+        assert!(world.get_component::<Health>(next).is_none());
+    }
+
+    #[test]
+    fn reserved_ids_do_not_collide_with_direct_spawns() {
+        let mut world = World::new();
+        let mut commands = Commands::new(&world);
+
+        let queued = commands.spawn().id();
+        let direct = world.new_entity();
+        assert_ne!(queued.index(), direct.index());
+
+        commands.apply(&mut world);
+        assert!(world.is_alive(queued));
+        assert!(world.is_alive(direct));
+    }
+}
@@ -1,13 +1,64 @@
-use crate::{math::transform::Transform, world::entity::Entity};
+use cgmath::{Quaternion, Vector3};
+use serde::{Deserialize, Serialize};
+use crate::{math::transform::Transform, world::{entity::Entity, snapshot::{EntityMap, MapEntities}}};
 
 #[allow(unused_variables)]
 pub trait Component {}
 
-#[derive(Clone, Copy)]
-pub struct Parent(pub Entity);
+// Only built by `World::set_parent`, which keeps `Parent` and `Children` in
+// sync. Removing either component unlinks the other side too.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Parent(pub(crate) Entity);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
 impl Component for Parent {}
 
-#[derive(Clone)]
+impl MapEntities for Parent {
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), String> {
+        self.0 = entity_map.map(self.0)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Children(pub(crate) Vec<Entity>);
+
+impl Children {
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+}
+
+impl Component for Children {}
+
+impl MapEntities for Children {
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), String> {
+        for child in self.0.iter_mut() {
+            *child = entity_map.map(*child)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "TransformData", into = "TransformData")]
 pub struct TransformComponent {
     pub transform: Transform
 }
@@ -21,3 +72,80 @@ impl TransformComponent {
 }
 
 impl Component for TransformComponent {}
+
+// Opt-in: the entity is drawn between its last two fixed-update states instead
+// of at the latest one, which hides the stutter of movement done in
+// `fixed_update` when the frame rate does not match the fixed rate.
+#[derive(Clone, Default)]
+pub struct TransformInterpolation {
+    pub(crate) previous: Option<Transform>
+}
+
+impl TransformInterpolation {
+    pub fn new() -> Self {
+        TransformInterpolation { previous: None }
+    }
+
+    // Skips blending until the next fixed step, e.g. after a teleport.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+impl Component for TransformInterpolation {}
+
+// Snapshot form of a transform: local values only, the matrices are rebuilt
+// by the transform system. Rotation is a quaternion as `[x, y, z, w]`.
+#[derive(Serialize, Deserialize)]
+struct TransformData {
+    position: [f32; 3],
+    rotation: [f32; 4],
+    scale: [f32; 3]
+}
+
+impl From<TransformComponent> for TransformData {
+    fn from(component: TransformComponent) -> Self {
+        let rotation = component.transform.get_local_rotation_quat();
+        TransformData {
+            position: (*component.transform.get_local_position()).into(),
+            rotation: [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s],
+            scale: (*component.transform.get_local_scale()).into()
+        }
+    }
+}
+
+impl From<TransformData> for TransformComponent {
+    fn from(data: TransformData) -> Self {
+        let [x, y, z, w] = data.rotation;
+        let mut component = TransformComponent::new();
+        component.transform.set_local_position(Vector3::from(data.position));
+        component.transform.set_local_rotation_quat(Quaternion::new(w, x, y, z));
+        component.transform.set_local_scale(Vector3::from(data.scale));
+        component
+    }
+}
+
+// Prefab description of a transform. Rotation is in Euler degrees, like `set_local_rotation`.
+#[derive(Deserialize, Debug)]
+pub struct TransformSerializer {
+    #[serde(default)]
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    #[serde(default = "TransformSerializer::default_scale")]
+    pub scale: [f32; 3]
+}
+
+impl TransformSerializer {
+    fn default_scale() -> [f32; 3] {
+        [1.0, 1.0, 1.0]
+    }
+
+    pub fn to_component(&self) -> TransformComponent {
+        let mut component = TransformComponent::new();
+        component.transform.set_local_position(Vector3::from(self.position));
+        component.transform.set_local_rotation(Vector3::from(self.rotation));
+        component.transform.set_local_scale(Vector3::from(self.scale));
+        component
+    }
+}
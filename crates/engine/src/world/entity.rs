@@ -1,2 +1,264 @@
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
-pub struct Entity(pub u32);
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+pub struct Entity {
+    index: u32,
+    generation: u32
+}
+
+impl Entity {
+    pub fn new(index: u32, generation: u32) -> Self {
+        Entity { index, generation }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+struct EntitySlot {
+    generation: u32,
+    alive: bool
+}
+
+// Ids that can be handed out: recycled ones first, then never used indices.
+// Ids reserved by `Commands` wait in `reserved` until the allocator is flushed.
+// Reservations given up after a flush wait in `cancelled` to be despawned.
+struct FreeIds {
+    free_list: Vec<Entity>,
+    next_index: u32,
+    reserved: Vec<Entity>,
+    cancelled: Vec<Entity>
+}
+
+impl FreeIds {
+    fn take(&mut self) -> Entity {
+        if let Some(entity) = self.free_list.pop() {
+            return entity;
+        }
+
+        self.next_index += 1;
+        Entity::new(self.next_index - 1, 0)
+    }
+}
+
+// Shared handle reserving entity ids without a mutable borrow of the world,
+// so that systems, even running in parallel, know the ids of what they spawn.
+#[derive(Clone)]
+pub struct EntityReserver {
+    ids: Arc<Mutex<FreeIds>>
+}
+
+impl EntityReserver {
+    // The id is not alive until the allocator is flushed.
+    pub fn reserve(&self) -> Entity {
+        let mut ids = self.ids.lock().unwrap();
+        let entity = ids.take();
+        ids.reserved.push(entity);
+        entity
+    }
+
+    // Gives back a reserved id that will never be used, such as the spawns of
+    // `Commands` dropped without being applied.
+    pub fn cancel(&self, entity: Entity) {
+        let mut ids = self.ids.lock().unwrap();
+        match ids.reserved.iter().position(|reserved| *reserved == entity) {
+            Some(index) => {
+                ids.reserved.swap_remove(index);
+                ids.free_list.push(entity);
+            }
+            // Already flushed: alive, but with nothing to refer to it
+            None => ids.cancelled.push(entity)
+        }
+    }
+
+    pub fn is_same(&self, other: &EntityReserver) -> bool {
+        Arc::ptr_eq(&self.ids, &other.ids)
+    }
+}
+
+// Hands out entity ids and recycles the indices of despawned entities.
+// Each reuse bumps the slot generation so stale handles no longer match.
+pub struct EntityAllocator {
+    slots: Vec<EntitySlot>,
+    ids: Arc<Mutex<FreeIds>>,
+    alive_count: usize
+}
+
+impl Default for EntityAllocator {
+    fn default() -> Self {
+        EntityAllocator::new()
+    }
+}
+
+impl EntityAllocator {
+    pub fn new() -> Self {
+        EntityAllocator {
+            slots: Vec::new(),
+            ids: Arc::new(Mutex::new(FreeIds {
+                free_list: Vec::new(),
+                next_index: 0,
+                reserved: Vec::new(),
+                cancelled: Vec::new()
+            })),
+            alive_count: 0
+        }
+    }
+
+    pub fn allocate(&mut self) -> Entity {
+        let entity = self.ids.lock().unwrap().take();
+        self.make_alive(entity);
+        entity
+    }
+
+    pub fn reserver(&self) -> EntityReserver {
+        EntityReserver { ids: self.ids.clone() }
+    }
+
+    // Makes the reserved ids alive. Returns the cancelled ones that were
+    // already alive, for the world to despawn.
+    pub fn flush(&mut self) -> Vec<Entity> {
+        let (reserved, cancelled) = {
+            let mut ids = self.ids.lock().unwrap();
+            (std::mem::take(&mut ids.reserved), std::mem::take(&mut ids.cancelled))
+        };
+        for entity in reserved {
+            self.make_alive(entity);
+        }
+        cancelled
+    }
+
+    fn make_alive(&mut self, entity: Entity) {
+        let index = entity.index as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || EntitySlot { generation: 0, alive: false });
+        }
+
+        self.slots[index] = EntitySlot { generation: entity.generation, alive: true };
+        self.alive_count += 1;
+    }
+
+    pub fn free(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        let slot = &mut self.slots[entity.index as usize];
+        slot.alive = false;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.ids.lock().unwrap().free_list.push(Entity::new(entity.index, slot.generation));
+        self.alive_count -= 1;
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.slots
+            .get(entity.index as usize)
+            .is_some_and(|slot| slot.alive && slot.generation == entity.generation)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.alive)
+            .map(|(index, slot)| Entity::new(index as u32, slot.generation))
+    }
+
+    pub fn alive_count(&self) -> usize {
+        self.alive_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freed_index_is_reused_with_next_generation() {
+        let mut allocator = EntityAllocator::new();
+        let first = allocator.allocate();
+        let second = allocator.allocate();
+
+        assert!(allocator.free(first));
+        let reused = allocator.allocate();
+
+        assert_eq!(reused.index(), first.index());
+        assert_eq!(reused.generation(), first.generation() + 1);
+        assert_ne!(reused, first);
+        assert_eq!(allocator.allocate().index(), 2);
+        assert!(allocator.is_alive(second));
+        assert_eq!(allocator.alive_count(), 3);
+    }
+
+    #[test]
+    fn stale_handle_is_rejected() {
+        let mut allocator = EntityAllocator::new();
+        let entity = allocator.allocate();
+        allocator.free(entity);
+
+        assert!(!allocator.is_alive(entity));
+        assert!(!allocator.free(entity));
+
+        let reused = allocator.allocate();
+        assert!(allocator.is_alive(reused));
+        assert!(!allocator.is_alive(entity));
+        assert!(!allocator.free(entity));
+        assert!(allocator.is_alive(reused));
+        assert_eq!(allocator.iter().collect::<Vec<_>>(), vec![reused]);
+    }
+
+    #[test]
+    fn reserved_ids_are_alive_after_flush() {
+        let mut allocator = EntityAllocator::new();
+        let freed = allocator.allocate();
+        allocator.free(freed);
+
+        let reserver = allocator.reserver();
+        let recycled = reserver.reserve();
+        let fresh = reserver.reserve();
+        assert_eq!(recycled, Entity::new(freed.index(), 1));
+        assert_eq!(fresh.index(), 1);
+        assert!(!allocator.is_alive(recycled));
+
+        // Reserved ids are never handed out twice
+        assert_eq!(allocator.allocate().index(), 2);
+
+        allocator.flush();
+        assert!(allocator.is_alive(recycled));
+        assert!(allocator.is_alive(fresh));
+        assert_eq!(allocator.alive_count(), 3);
+    }
+
+    #[test]
+    fn cancelled_reservations_are_recycled() {
+        let mut allocator = EntityAllocator::new();
+        let reserver = allocator.reserver();
+        let cancelled = reserver.reserve();
+        let kept = reserver.reserve();
+
+        reserver.cancel(cancelled);
+        assert!(allocator.flush().is_empty());
+        assert!(!allocator.is_alive(cancelled));
+        assert!(allocator.is_alive(kept));
+        assert_eq!(allocator.allocate(), cancelled);
+
+        // Cancelled after a flush: handed back to be despawned
+        let late = reserver.reserve();
+        allocator.flush();
+        reserver.cancel(late);
+        assert_eq!(allocator.flush(), vec![late]);
+    }
+
+    #[test]
+    fn unknown_index_is_not_alive() {
+        let allocator = EntityAllocator::new();
+        assert!(!allocator.is_alive(Entity::new(3, 0)));
+    }
+}
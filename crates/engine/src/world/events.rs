@@ -0,0 +1,146 @@
+use std::marker::PhantomData;
+
+// Double-buffered event queue stored as a world resource. Events stay readable
+// for the frame they are sent in and the following one, so a reader running
+// before the writer in the frame still sees them once.
+pub struct Events<T> {
+    previous: Vec<(usize, T)>,
+    current: Vec<(usize, T)>,
+    event_count: usize
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Events::new()
+    }
+}
+
+#[allow(dead_code)]
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Events {
+            previous: Vec::new(),
+            current: Vec::new(),
+            event_count: 0
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push((self.event_count, event));
+        self.event_count += 1;
+    }
+
+    // Drops the events of the previous frame and starts a new buffer.
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(self.current.iter()).map(|(_, event)| event)
+    }
+}
+
+// Remembers which events of a channel its owner has already seen. Each system
+// that consumes a channel keeps its own reader.
+pub struct EventReader<T> {
+    last_event_count: usize,
+    _marker: PhantomData<fn() -> T>
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        EventReader::new()
+    }
+}
+
+impl<T> EventReader<T> {
+    pub fn new() -> Self {
+        EventReader {
+            last_event_count: 0,
+            _marker: PhantomData
+        }
+    }
+
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> + use<'a, T> {
+        let last_event_count = self.last_event_count;
+        self.last_event_count = events.event_count;
+
+        events.previous.iter()
+            .chain(events.current.iter())
+            .filter(move |(id, _)| *id >= last_event_count)
+            .map(|(_, event)| event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(reader: &mut EventReader<u32>, events: &Events<u32>) -> Vec<u32> {
+        reader.read(events).copied().collect()
+    }
+
+    #[test]
+    fn event_survives_exactly_one_update() {
+        let mut events = Events::new();
+        events.send(1);
+        assert_eq!(events.iter().collect::<Vec<_>>(), [&1]);
+
+        events.update();
+        events.send(2);
+        assert_eq!(events.iter().collect::<Vec<_>>(), [&1, &2]);
+
+        events.update();
+        assert_eq!(events.iter().collect::<Vec<_>>(), [&2]);
+
+        events.update();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn readers_have_their_own_cursor() {
+        let mut events = Events::new();
+        let mut early = EventReader::new();
+        let mut late = EventReader::new();
+
+        events.send(1);
+        events.send(2);
+        assert_eq!(read(&mut early, &events), [1, 2]);
+        assert!(read(&mut early, &events).is_empty());
+
+        events.update();
+        events.send(3);
+        assert_eq!(read(&mut early, &events), [3]);
+        assert_eq!(read(&mut late, &events), [1, 2, 3]);
+
+        events.update();
+        events.send(4);
+        assert_eq!(read(&mut early, &events), [4]);
+        assert_eq!(read(&mut late, &events), [4]);
+    }
+
+    #[test]
+    fn reader_missing_two_updates_loses_events() {
+        let mut events = Events::new();
+        let mut reader = EventReader::new();
+
+        events.send(1);
+        events.update();
+        events.update();
+        events.send(2);
+        assert_eq!(read(&mut reader, &events), [2]);
+    }
+}
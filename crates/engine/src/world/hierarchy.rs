@@ -0,0 +1,275 @@
+use std::any::Any;
+
+use crate::world::components::{Children, Parent};
+use crate::world::entity::Entity;
+use crate::world::world::World;
+
+impl World {
+    // Attaches `child` under `parent`, detaching it from its previous parent first.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        if !self.is_alive(child) || !self.is_alive(parent) {
+            eprintln!("Cannot parent {:?} to {:?}: both entities must be alive", child, parent);
+            return;
+        }
+
+        if child == parent || self.is_ancestor_of(child, parent) {
+            eprintln!("Cannot parent {:?} to {:?}: it would create a cycle", child, parent);
+            return;
+        }
+
+        self.remove_parent(child);
+        self.add_component(child, Parent(parent));
+
+        match self.get_component_mut::<Children>(parent) {
+            Some(children) => children.0.push(child),
+            None => self.add_component(parent, Children(vec![child]))
+        }
+    }
+
+    // Makes `child` a root again. It keeps its local transform.
+    pub fn remove_parent(&mut self, child: Entity) {
+        self.remove_component::<Parent>(child);
+    }
+
+    // Called by `remove_component` so that removing one side of a link also
+    // removes the other.
+    pub(crate) fn unlink_removed(&mut self, entity: Entity, removed: &dyn Any) {
+        if let Some(Parent(parent)) = removed.downcast_ref::<Parent>() {
+            self.remove_child(*parent, entity);
+        } else if let Some(children) = removed.downcast_ref::<Children>() {
+            for child in children.iter() {
+                if self.get_component::<Parent>(child).is_some_and(|parent| parent.0 == entity) {
+                    self.remove_component::<Parent>(child);
+                }
+            }
+        }
+    }
+
+    fn remove_child(&mut self, parent: Entity, child: Entity) {
+        let Some(children) = self.get_component_mut::<Children>(parent) else {
+            return;
+        };
+
+        children.0.retain(|&other| other != child);
+        if children.0.is_empty() {
+            self.remove_component::<Children>(parent);
+        }
+    }
+
+    pub fn is_ancestor_of(&self, ancestor: Entity, entity: Entity) -> bool {
+        let mut current = self.get_component::<Parent>(entity).map(|parent| parent.0);
+        while let Some(parent) = current {
+            if parent == ancestor {
+                return true;
+            }
+            current = self.get_component::<Parent>(parent).map(|parent| parent.0);
+        }
+        false
+    }
+
+    // Every entity below `entity`, parents always listed before their children.
+    pub fn descendants(&self, entity: Entity) -> Vec<Entity> {
+        let mut descendants = Vec::new();
+        let mut next = 0;
+        let mut current = Some(entity);
+
+        while let Some(parent) = current {
+            if let Some(children) = self.get_component::<Children>(parent) {
+                descendants.extend(children.iter());
+            }
+            current = descendants.get(next).copied();
+            next += 1;
+        }
+
+        descendants
+    }
+
+    // Despawns `entity` along with all of its descendants.
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        let descendants = self.descendants(entity);
+        self.remove_entity(entity);
+        for descendant in descendants {
+            self.remove_entity(descendant);
+        }
+    }
+
+    // Unlinks an entity that is about to be despawned from its parent and children.
+    pub(crate) fn detach_from_hierarchy(&mut self, entity: Entity) {
+        self.remove_component::<Parent>(entity);
+        self.remove_component::<Children>(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+    use crate::core::frame_context::FrameContext;
+    use crate::core::input::InputHandler;
+    use crate::core::time::Time;
+    use crate::world::commands::Commands;
+    use crate::world::components::TransformComponent;
+    use crate::world::system::{System, TransformSystem};
+
+    fn spawn_at(world: &mut World, x: f32) -> Entity {
+        let entity = world.new_entity();
+        let mut transform = TransformComponent::new();
+        transform.transform.set_local_position(Vector3::new(x, 0.0, 0.0));
+        world.add_component(entity, transform);
+        entity
+    }
+
+    fn propagate(world: &mut World, last_run: &mut u64) {
+        let time = Time::new();
+        let input = InputHandler::new();
+        let mut commands = Commands::new(world);
+        *last_run = world.begin_system_run(*last_run);
+        TransformSystem.update(&mut FrameContext { time: &time, input: &input, world, commands: &mut commands });
+        world.end_system_run();
+    }
+
+    fn global_x(world: &World, entity: Entity) -> f32 {
+        world.get_component::<TransformComponent>(entity).unwrap().transform.get_global_position().x
+    }
+
+    fn children(world: &World, entity: Entity) -> Vec<Entity> {
+        world.get_component::<Children>(entity).map(|children| children.iter().collect()).unwrap_or_default()
+    }
+
+    #[test]
+    fn propagates_through_deep_hierarchy() {
+        let mut world = World::new();
+        let mut last_run = 0;
+
+        let chain: Vec<Entity> = (0..5).map(|_| spawn_at(&mut world, 1.0)).collect();
+        for pair in chain.windows(2) {
+            world.set_parent(pair[1], pair[0]);
+        }
+        propagate(&mut world, &mut last_run);
+
+        for (depth, entity) in chain.iter().enumerate() {
+            assert_eq!(global_x(&world, *entity), depth as f32 + 1.0);
+        }
+
+        // Moving the root alone moves the whole chain on the next run
+        world.get_component_mut::<TransformComponent>(chain[0]).unwrap()
+            .transform.set_local_position(Vector3::new(10.0, 0.0, 0.0));
+        propagate(&mut world, &mut last_run);
+        assert_eq!(global_x(&world, chain[4]), 14.0);
+    }
+
+    #[test]
+    fn propagates_through_untransformed_ancestors() {
+        let mut world = World::new();
+        let mut last_run = 0;
+
+        // Root and middle entities without a transform pass matrices through
+        let root = world.new_entity();
+        let group = world.new_entity();
+        let parent = spawn_at(&mut world, 2.0);
+        let child = spawn_at(&mut world, 3.0);
+        world.set_parent(group, root);
+        world.set_parent(parent, group);
+        world.set_parent(child, parent);
+        propagate(&mut world, &mut last_run);
+
+        assert_eq!(global_x(&world, parent), 2.0);
+        assert_eq!(global_x(&world, child), 5.0);
+    }
+
+    #[test]
+    fn reparenting_moves_the_subtree() {
+        let mut world = World::new();
+        let mut last_run = 0;
+
+        let left = spawn_at(&mut world, -10.0);
+        let right = spawn_at(&mut world, 10.0);
+        let child = spawn_at(&mut world, 1.0);
+        let grandchild = spawn_at(&mut world, 1.0);
+        world.set_parent(child, left);
+        world.set_parent(grandchild, child);
+        propagate(&mut world, &mut last_run);
+        assert_eq!(global_x(&world, grandchild), -8.0);
+
+        world.set_parent(child, right);
+        assert_eq!(children(&world, left), vec![]);
+        assert_eq!(children(&world, right), vec![child]);
+        propagate(&mut world, &mut last_run);
+        assert_eq!(global_x(&world, grandchild), 12.0);
+
+        world.remove_parent(child);
+        assert!(world.get_component::<Children>(right).is_none());
+        propagate(&mut world, &mut last_run);
+        assert_eq!(global_x(&world, child), 1.0);
+        assert_eq!(global_x(&world, grandchild), 2.0);
+    }
+
+    #[test]
+    fn cycles_are_refused() {
+        let mut world = World::new();
+        let root = world.new_entity();
+        let child = world.new_entity();
+        let grandchild = world.new_entity();
+        world.set_parent(child, root);
+        world.set_parent(grandchild, child);
+
+        world.set_parent(root, grandchild);
+        world.set_parent(child, child);
+
+        assert!(world.get_component::<Parent>(root).is_none());
+        assert_eq!(world.get_component::<Parent>(child).map(|parent| parent.get()), Some(root));
+    }
+
+    #[test]
+    fn removing_one_side_unlinks_the_other() {
+        let mut world = World::new();
+        let parent = world.new_entity();
+        let first = world.new_entity();
+        let second = world.new_entity();
+        world.set_parent(first, parent);
+        world.set_parent(second, parent);
+
+        world.remove_component::<Parent>(first);
+        assert_eq!(children(&world, parent), vec![second]);
+
+        world.remove_component::<Children>(parent);
+        assert!(world.get_component::<Parent>(second).is_none());
+    }
+
+    #[test]
+    fn despawn_recursive_removes_the_subtree_only() {
+        let mut world = World::new();
+        let root = world.new_entity();
+        let branch = world.new_entity();
+        let leaf = world.new_entity();
+        let deep_leaf = world.new_entity();
+        let sibling = world.new_entity();
+        world.set_parent(branch, root);
+        world.set_parent(leaf, branch);
+        world.set_parent(deep_leaf, leaf);
+        world.set_parent(sibling, root);
+
+        assert_eq!(world.descendants(branch), vec![leaf, deep_leaf]);
+        world.despawn_recursive(branch);
+
+        assert!(!world.is_alive(branch));
+        assert!(!world.is_alive(leaf));
+        assert!(!world.is_alive(deep_leaf));
+        assert!(world.is_alive(sibling));
+        assert_eq!(children(&world, root), vec![sibling]);
+    }
+
+    #[test]
+    fn despawning_a_parent_orphans_its_children() {
+        let mut world = World::new();
+        let parent = world.new_entity();
+        let child = world.new_entity();
+        world.set_parent(child, parent);
+
+        world.remove_entity(parent);
+
+        assert!(world.is_alive(child));
+        assert!(world.get_component::<Parent>(child).is_none());
+    }
+}
@@ -1,4 +1,14 @@
 pub mod entity;
 pub mod components;
 pub mod world;
-pub mod system;
\ No newline at end of file
+pub mod system;
+pub mod query;
+pub mod storage;
+pub mod commands;
+pub mod events;
+pub mod schedule;
+pub mod change_detection;
+pub mod hierarchy;
+pub mod bundle;
+pub mod prefab;
+pub mod snapshot;
\ No newline at end of file
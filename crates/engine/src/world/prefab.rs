@@ -0,0 +1,250 @@
+use std::{fmt, fs::File, io::Read};
+
+use hashbrown::HashMap;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Value};
+
+use crate::core::path::get_path_to_asset;
+use crate::world::components::Component;
+use crate::world::entity::Entity;
+use crate::world::world::World;
+
+type ComponentDeserializer = Box<dyn Fn(&mut World, Entity, &Value) -> Result<(), String> + Send + Sync>;
+
+// Maps the component names used in prefab files to the code that builds and
+// inserts the component from its JSON fields.
+pub struct ComponentRegistry {
+    deserializers: HashMap<String, ComponentDeserializer>
+}
+
+impl Default for ComponentRegistry {
+    fn default() -> Self {
+        ComponentRegistry::new()
+    }
+}
+
+#[allow(dead_code)]
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        ComponentRegistry {
+            deserializers: HashMap::new()
+        }
+    }
+
+    // For components whose JSON form is the component itself.
+    pub fn register<T: Component + DeserializeOwned + 'static + Send + Sync>(&mut self, name: &str) {
+        self.register_with(name, |world, entity, value| {
+            let component = T::deserialize(value).map_err(|e| e.to_string())?;
+            world.add_component(entity, component);
+            Ok(())
+        });
+    }
+
+    pub fn register_with<F>(&mut self, name: &str, deserializer: F)
+    where
+        F: Fn(&mut World, Entity, &Value) -> Result<(), String> + Send + Sync + 'static
+    {
+        if self.deserializers.insert(name.to_string(), Box::new(deserializer)).is_some() {
+            eprintln!("Component '{}' was already registered for prefabs, replacing it", name);
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.deserializers.contains_key(name)
+    }
+
+    // Spawns one entity with every component of the prefab. Nothing is left
+    // behind if one of them fails to load.
+    pub fn spawn(&self, world: &mut World, prefab: &Prefab) -> Result<Entity, PrefabError> {
+        if let Some(component) = prefab.components.keys().find(|name| !self.contains(name)) {
+            return Err(PrefabError::UnknownComponent { prefab: prefab.name.clone(), component: component.clone() });
+        }
+
+        let entity = world.new_entity();
+        for (name, value) in &prefab.components {
+            if let Err(message) = (self.deserializers[name])(world, entity, value) {
+                world.remove_entity(entity);
+                return Err(PrefabError::InvalidComponent { prefab: prefab.name.clone(), component: name.clone(), message });
+            }
+        }
+
+        Ok(entity)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Prefab {
+    pub name: String,
+    pub components: Map<String, Value>
+}
+
+pub struct PrefabManager {
+    prefabs: HashMap<String, Prefab>
+}
+
+impl Default for PrefabManager {
+    fn default() -> Self {
+        PrefabManager::new()
+    }
+}
+
+impl PrefabManager {
+    pub fn new() -> Self {
+        PrefabManager {
+            prefabs: HashMap::new()
+        }
+    }
+
+    pub fn load(&mut self, metadata_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = File::open(get_path_to_asset(metadata_path))?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let prefab: Prefab = serde_json::from_str(&contents)?;
+
+        self.add(prefab)?;
+        Ok(())
+    }
+
+    // Two prefabs cannot share a name, the first one loaded is kept.
+    pub fn add(&mut self, prefab: Prefab) -> Result<(), PrefabError> {
+        if self.prefabs.contains_key(&prefab.name) {
+            return Err(PrefabError::DuplicatePrefab(prefab.name));
+        }
+
+        self.prefabs.insert(prefab.name.clone(), prefab);
+        Ok(())
+    }
+
+    // Loads every `.json` file of the directory, so new prefabs only need a file.
+    pub fn load_directory(&mut self, directory_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut paths: Vec<_> = std::fs::read_dir(get_path_to_asset(directory_path))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+            self.load(&format!("{}/{}", directory_path, file_name))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+}
+
+#[derive(Debug)]
+pub enum PrefabError {
+    UnknownPrefab(String),
+    DuplicatePrefab(String),
+    UnknownComponent { prefab: String, component: String },
+    InvalidComponent { prefab: String, component: String, message: String },
+    MissingResource(&'static str)
+}
+
+impl fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrefabError::UnknownPrefab(name) => write!(f, "no prefab named '{}' is loaded", name),
+            PrefabError::DuplicatePrefab(name) => write!(f, "a prefab named '{}' is already loaded", name),
+            PrefabError::UnknownComponent { prefab, component } => {
+                write!(f, "prefab '{}' uses unregistered component '{}'", prefab, component)
+            }
+            PrefabError::InvalidComponent { prefab, component, message } => {
+                write!(f, "component '{}' of prefab '{}' is invalid: {}", component, prefab, message)
+            }
+            PrefabError::MissingResource(resource) => write!(f, "the world has no {} resource", resource)
+        }
+    }
+}
+
+impl std::error::Error for PrefabError {}
+
+impl World {
+    pub fn spawn_prefab(&mut self, name: &str) -> Result<Entity, PrefabError> {
+        self.resource_scope(|world, prefabs: &mut PrefabManager| {
+            let prefab = prefabs.get(name).ok_or_else(|| PrefabError::UnknownPrefab(name.to_string()))?;
+
+            world.resource_scope(|world, registry: &mut ComponentRegistry| registry.spawn(world, prefab))
+                .unwrap_or(Err(PrefabError::MissingResource("ComponentRegistry")))
+        })
+        .unwrap_or(Err(PrefabError::MissingResource("PrefabManager")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Speed(f32);
+    impl Component for Speed {}
+
+    fn prefab(name: &str, components: Value) -> Prefab {
+        serde_json::from_value(json!({ "name": name, "components": components })).unwrap()
+    }
+
+    fn world_with(prefabs: Vec<Prefab>) -> World {
+        let mut world = World::new();
+        let mut registry = ComponentRegistry::new();
+        registry.register::<Health>("health");
+        registry.register::<Speed>("speed");
+        world.insert_resource(registry);
+
+        let mut manager = PrefabManager::new();
+        for prefab in prefabs {
+            manager.add(prefab).unwrap();
+        }
+        world.insert_resource(manager);
+        world
+    }
+
+    #[test]
+    fn spawns_every_component() {
+        let mut world = world_with(vec![prefab("runner", json!({ "health": 3, "speed": 1.5 }))]);
+
+        let entity = world.spawn_prefab("runner").unwrap();
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health(3)));
+        assert_eq!(world.get_component::<Speed>(entity), Some(&Speed(1.5)));
+        assert!(matches!(world.spawn_prefab("walker"), Err(PrefabError::UnknownPrefab(_))));
+    }
+
+    #[test]
+    fn unknown_component_spawns_nothing() {
+        let mut world = world_with(vec![prefab("flyer", json!({ "health": 3, "wings": 2 }))]);
+
+        let error = world.spawn_prefab("flyer").unwrap_err();
+        assert!(matches!(&error, PrefabError::UnknownComponent { component, .. } if component == "wings"), "{}", error);
+        assert_eq!(world.entity_count(), 0);
+    }
+
+    #[test]
+    fn invalid_component_rolls_back_the_entity() {
+        // "health" is inserted before "speed" fails
+        let mut world = world_with(vec![prefab("broken", json!({ "health": 3, "speed": "fast" }))]);
+
+        let error = world.spawn_prefab("broken").unwrap_err();
+        assert!(matches!(&error, PrefabError::InvalidComponent { component, .. } if component == "speed"), "{}", error);
+        assert_eq!(world.entity_count(), 0);
+        assert!(world.get_components::<Health>().is_some_and(|health| health.is_empty()));
+    }
+
+    #[test]
+    fn duplicate_name_is_refused() {
+        let mut manager = PrefabManager::new();
+        manager.add(prefab("runner", json!({ "health": 3 }))).unwrap();
+
+        let error = manager.add(prefab("runner", json!({ "health": 5 }))).unwrap_err();
+        assert!(matches!(error, PrefabError::DuplicatePrefab(ref name) if name == "runner"));
+        assert_eq!(manager.get("runner").unwrap().components["health"], json!(3));
+    }
+}
@@ -0,0 +1,651 @@
+use std::any::{type_name, TypeId};
+use std::marker::PhantomData;
+
+use crate::world::change_detection::{ComponentTicks, Mut};
+use crate::world::components::Component;
+use crate::world::entity::Entity;
+use crate::world::storage::SparseSet;
+use crate::world::world::{IComponentMap, World};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write
+}
+
+// Component types touched by a query, used to reject aliasing borrows
+// such as `(&mut A, &A)` before any storage is handed out.
+#[derive(Default, Clone)]
+pub struct Access {
+    entries: Vec<(TypeId, &'static str, AccessKind)>
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Access { entries: Vec::new() }
+    }
+
+    pub fn add_read<T: 'static>(&mut self) {
+        self.entries.push((TypeId::of::<T>(), type_name::<T>(), AccessKind::Read));
+    }
+
+    pub fn add_write<T: 'static>(&mut self) {
+        self.entries.push((TypeId::of::<T>(), type_name::<T>(), AccessKind::Write));
+    }
+
+    // Returns the name of the first type that is borrowed mutably more than once,
+    // or both mutably and immutably.
+    pub fn find_conflict(&self) -> Option<&'static str> {
+        for (i, (type_id, name, kind)) in self.entries.iter().enumerate() {
+            for (other_id, _, other_kind) in self.entries.iter().skip(i + 1) {
+                if type_id == other_id && (*kind == AccessKind::Write || *other_kind == AccessKind::Write) {
+                    return Some(name);
+                }
+            }
+        }
+        None
+    }
+
+    // Two access sets are compatible when neither writes something the other touches.
+    pub fn is_compatible(&self, other: &Access) -> bool {
+        self.entries.iter().all(|(type_id, _, kind)| {
+            other.entries.iter().all(|(other_id, _, other_kind)| {
+                type_id != other_id || (*kind == AccessKind::Read && *other_kind == AccessKind::Read)
+            })
+        })
+    }
+
+    pub(crate) fn writes(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.entries.iter()
+            .filter(|(_, _, kind)| *kind == AccessKind::Write)
+            .map(|(type_id, _, _)| *type_id)
+    }
+}
+
+type StoragePtr = *const dyn IComponentMap;
+
+// Storage whose entities are walked by a query: the smallest required one.
+pub struct QueryDriver {
+    best: Option<StoragePtr>
+}
+
+impl QueryDriver {
+    fn consider(&mut self, candidate: StoragePtr) {
+        // SAFETY: driver pointers come from storages borrowed by the running query.
+        let shorter = match self.best {
+            Some(current) => unsafe { (*candidate).len() < (*current).len() },
+            None => true
+        };
+        if shorter {
+            self.best = Some(candidate);
+        }
+    }
+}
+
+/// A set of components fetched together for one entity.
+///
+/// # Safety
+/// `update_access` must report every component type `fetch` hands out, with the
+/// matching kind, so `World::query` can rule out aliasing references.
+pub unsafe trait WorldQuery {
+    type Item<'w>;
+    type Fetch: Copy;
+
+    fn update_access(access: &mut Access);
+    fn init_fetch(world: &mut World) -> Option<Self::Fetch>;
+    fn driver(fetch: &Self::Fetch, driver: &mut QueryDriver);
+
+    /// # Safety
+    /// The storages behind `fetch` must still be borrowed for `'w`, and the
+    /// same entity must not be fetched twice while mutable items are alive.
+    unsafe fn fetch<'w>(fetch: &Self::Fetch, entity: Entity) -> Option<Self::Item<'w>>;
+}
+
+/// A condition on the components of an entity that does not fetch any data.
+///
+/// # Safety
+/// Same contract as `WorldQuery`.
+pub unsafe trait QueryFilter {
+    type Fetch: Copy;
+
+    fn update_access(access: &mut Access);
+    fn init_fetch(world: &mut World) -> Option<Self::Fetch>;
+    fn driver(fetch: &Self::Fetch, driver: &mut QueryDriver);
+
+    /// # Safety
+    /// The storages behind `fetch` must still be borrowed.
+    unsafe fn matches(fetch: &Self::Fetch, entity: Entity) -> bool;
+}
+
+unsafe impl WorldQuery for Entity {
+    type Item<'w> = Entity;
+    type Fetch = ();
+
+    fn update_access(_access: &mut Access) {}
+
+    fn init_fetch(_world: &mut World) -> Option<Self::Fetch> {
+        Some(())
+    }
+
+    fn driver(_fetch: &Self::Fetch, _driver: &mut QueryDriver) {}
+
+    unsafe fn fetch<'w>(_fetch: &Self::Fetch, entity: Entity) -> Option<Self::Item<'w>> {
+        Some(entity)
+    }
+}
+
+unsafe impl<T: Component + 'static + Send + Sync> WorldQuery for &T {
+    type Item<'w> = &'w T;
+    type Fetch = *const SparseSet<T>;
+
+    fn update_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn init_fetch(world: &mut World) -> Option<Self::Fetch> {
+        world.storage_ptr_read::<T>()
+    }
+
+    fn driver(fetch: &Self::Fetch, driver: &mut QueryDriver) {
+        driver.consider(*fetch as StoragePtr);
+    }
+
+    unsafe fn fetch<'w>(fetch: &Self::Fetch, entity: Entity) -> Option<Self::Item<'w>> {
+        unsafe { (**fetch).get(entity) }
+    }
+}
+
+pub struct MutFetch<T> {
+    storage: *const SparseSet<T>,
+    data: *mut T,
+    ticks: *mut ComponentTicks,
+    last_run: u64,
+    this_run: u64
+}
+
+// Not derived: the derive would require `T: Copy`.
+impl<T> Clone for MutFetch<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for MutFetch<T> {}
+
+unsafe impl<T: Component + 'static + Send + Sync> WorldQuery for &mut T {
+    type Item<'w> = Mut<'w, T>;
+    // The data pointers are taken once from a mutable borrow so that items can be
+    // handed out without reborrowing the whole storage for every entity.
+    type Fetch = MutFetch<T>;
+
+    fn update_access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    fn init_fetch(world: &mut World) -> Option<Self::Fetch> {
+        let last_run = world.last_change_tick();
+        let this_run = world.change_tick();
+        world.storage_ptr::<T>().map(|ptr| unsafe {
+            MutFetch {
+                storage: ptr as *const _,
+                data: (*ptr).data_ptr(),
+                ticks: (*ptr).ticks_ptr(),
+                last_run,
+                this_run
+            }
+        })
+    }
+
+    fn driver(fetch: &Self::Fetch, driver: &mut QueryDriver) {
+        driver.consider(fetch.storage as StoragePtr);
+    }
+
+    unsafe fn fetch<'w>(fetch: &Self::Fetch, entity: Entity) -> Option<Self::Item<'w>> {
+        unsafe {
+            let dense_index = (*fetch.storage).dense_index(entity)?;
+            Some(Mut {
+                value: &mut *fetch.data.add(dense_index),
+                ticks: &mut *fetch.ticks.add(dense_index),
+                last_run: fetch.last_run,
+                this_run: fetch.this_run
+            })
+        }
+    }
+}
+
+unsafe impl<Q: WorldQuery> WorldQuery for Option<Q> {
+    type Item<'w> = Option<Q::Item<'w>>;
+    type Fetch = Option<Q::Fetch>;
+
+    fn update_access(access: &mut Access) {
+        Q::update_access(access);
+    }
+
+    fn init_fetch(world: &mut World) -> Option<Self::Fetch> {
+        Some(Q::init_fetch(world))
+    }
+
+    fn driver(_fetch: &Self::Fetch, _driver: &mut QueryDriver) {}
+
+    unsafe fn fetch<'w>(fetch: &Self::Fetch, entity: Entity) -> Option<Self::Item<'w>> {
+        match fetch {
+            Some(inner) => Some(unsafe { Q::fetch(inner, entity) }),
+            None => Some(None)
+        }
+    }
+}
+
+pub struct With<T>(PhantomData<T>);
+
+unsafe impl<T: Component + 'static + Send + Sync> QueryFilter for With<T> {
+    type Fetch = *const SparseSet<T>;
+
+    fn update_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn init_fetch(world: &mut World) -> Option<Self::Fetch> {
+        world.storage_ptr_read::<T>()
+    }
+
+    fn driver(fetch: &Self::Fetch, driver: &mut QueryDriver) {
+        driver.consider(*fetch as StoragePtr);
+    }
+
+    unsafe fn matches(fetch: &Self::Fetch, entity: Entity) -> bool {
+        unsafe { (**fetch).contains(entity) }
+    }
+}
+
+// Entities whose `T` was inserted since the system last ran.
+pub struct Added<T>(PhantomData<T>);
+
+unsafe impl<T: Component + 'static + Send + Sync> QueryFilter for Added<T> {
+    type Fetch = (*const SparseSet<T>, u64);
+
+    fn update_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn init_fetch(world: &mut World) -> Option<Self::Fetch> {
+        let last_run = world.last_change_tick();
+        world.storage_ptr_read::<T>().map(|ptr| (ptr, last_run))
+    }
+
+    fn driver(fetch: &Self::Fetch, driver: &mut QueryDriver) {
+        driver.consider(fetch.0 as StoragePtr);
+    }
+
+    unsafe fn matches(fetch: &Self::Fetch, entity: Entity) -> bool {
+        let (storage, last_run) = *fetch;
+        unsafe { (*storage).ticks(entity).is_some_and(|ticks| ticks.is_added(last_run)) }
+    }
+}
+
+// Entities whose `T` was inserted or mutated since the system last ran.
+pub struct Changed<T>(PhantomData<T>);
+
+unsafe impl<T: Component + 'static + Send + Sync> QueryFilter for Changed<T> {
+    type Fetch = (*const SparseSet<T>, u64);
+
+    fn update_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn init_fetch(world: &mut World) -> Option<Self::Fetch> {
+        let last_run = world.last_change_tick();
+        world.storage_ptr_read::<T>().map(|ptr| (ptr, last_run))
+    }
+
+    fn driver(fetch: &Self::Fetch, driver: &mut QueryDriver) {
+        driver.consider(fetch.0 as StoragePtr);
+    }
+
+    unsafe fn matches(fetch: &Self::Fetch, entity: Entity) -> bool {
+        let (storage, last_run) = *fetch;
+        unsafe { (*storage).ticks(entity).is_some_and(|ticks| ticks.is_changed(last_run)) }
+    }
+}
+
+pub struct Without<T>(PhantomData<T>);
+
+unsafe impl<T: Component + 'static + Send + Sync> QueryFilter for Without<T> {
+    type Fetch = Option<*const SparseSet<T>>;
+
+    fn update_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn init_fetch(world: &mut World) -> Option<Self::Fetch> {
+        Some(world.storage_ptr_read::<T>())
+    }
+
+    fn driver(_fetch: &Self::Fetch, _driver: &mut QueryDriver) {}
+
+    unsafe fn matches(fetch: &Self::Fetch, entity: Entity) -> bool {
+        match fetch {
+            Some(ptr) => unsafe { !(**ptr).contains(entity) },
+            None => true
+        }
+    }
+}
+
+macro_rules! impl_query_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case)]
+        unsafe impl<$($name: WorldQuery),*> WorldQuery for ($($name,)*) {
+            type Item<'w> = ($($name::Item<'w>,)*);
+            type Fetch = ($($name::Fetch,)*);
+
+            fn update_access(access: &mut Access) {
+                $($name::update_access(access);)*
+            }
+
+            fn init_fetch(world: &mut World) -> Option<Self::Fetch> {
+                Some(($($name::init_fetch(world)?,)*))
+            }
+
+            fn driver(fetch: &Self::Fetch, driver: &mut QueryDriver) {
+                let ($($name,)*) = fetch;
+                $($name::driver($name, driver);)*
+            }
+
+            unsafe fn fetch<'w>(fetch: &Self::Fetch, entity: Entity) -> Option<Self::Item<'w>> {
+                let ($($name,)*) = fetch;
+                Some(($(unsafe { $name::fetch($name, entity)? },)*))
+            }
+        }
+
+        #[allow(non_snake_case)]
+        unsafe impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {
+            type Fetch = ($($name::Fetch,)*);
+
+            fn update_access(access: &mut Access) {
+                $($name::update_access(access);)*
+            }
+
+            fn init_fetch(world: &mut World) -> Option<Self::Fetch> {
+                Some(($($name::init_fetch(world)?,)*))
+            }
+
+            fn driver(fetch: &Self::Fetch, driver: &mut QueryDriver) {
+                let ($($name,)*) = fetch;
+                $($name::driver($name, driver);)*
+            }
+
+            unsafe fn matches(fetch: &Self::Fetch, entity: Entity) -> bool {
+                let ($($name,)*) = fetch;
+                true $(&& unsafe { $name::matches($name, entity) })*
+            }
+        }
+    };
+}
+
+unsafe impl QueryFilter for () {
+    type Fetch = ();
+
+    fn update_access(_access: &mut Access) {}
+
+    fn init_fetch(_world: &mut World) -> Option<Self::Fetch> {
+        Some(())
+    }
+
+    fn driver(_fetch: &Self::Fetch, _driver: &mut QueryDriver) {}
+
+    unsafe fn matches(_fetch: &Self::Fetch, _entity: Entity) -> bool {
+        true
+    }
+}
+
+impl_query_tuple!(A);
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
+impl_query_tuple!(A, B, C, D, E, F, G);
+impl_query_tuple!(A, B, C, D, E, F, G, H);
+impl_query_tuple!(A, B, C, D, E, F, G, H, I);
+impl_query_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_query_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_query_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+enum Candidates {
+    // Dense entity array of the driving storage, walked in memory order.
+    Storage(*const [Entity]),
+    // Every live entity, used when no component is required.
+    All(Vec<Entity>)
+}
+
+impl Candidates {
+    fn get(&self, index: usize) -> Option<Entity> {
+        match self {
+            // SAFETY: the storage is borrowed by the query and cannot change shape.
+            Candidates::Storage(entities) => unsafe { (&**entities).get(index).copied() },
+            Candidates::All(entities) => entities.get(index).copied()
+        }
+    }
+}
+
+pub struct Query<'w, Q: WorldQuery, F: QueryFilter = ()> {
+    fetch: Option<(Q::Fetch, F::Fetch)>,
+    entities: Candidates,
+    cursor: usize,
+    _marker: PhantomData<&'w mut World>
+}
+
+impl<'w, Q: WorldQuery, F: QueryFilter> Query<'w, Q, F> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        let mut access = Access::new();
+        Q::update_access(&mut access);
+        F::update_access(&mut access);
+        if let Some(name) = access.find_conflict() {
+            panic!("Query {} borrows {} mutably more than once", type_name::<(Q, F)>(), name);
+        }
+
+        let fetch = match (Q::init_fetch(world), F::init_fetch(world)) {
+            (Some(query_fetch), Some(filter_fetch)) => Some((query_fetch, filter_fetch)),
+            _ => None
+        };
+
+        let entities = match &fetch {
+            Some((query_fetch, filter_fetch)) => {
+                let mut driver = QueryDriver { best: None };
+                Q::driver(query_fetch, &mut driver);
+                F::driver(filter_fetch, &mut driver);
+
+                match driver.best {
+                    // SAFETY: the driver points into a storage of `world`, which we borrow.
+                    Some(storage) => Candidates::Storage(unsafe { (*storage).entities() } as *const [Entity]),
+                    None => Candidates::All(world.entities().collect())
+                }
+            }
+            None => Candidates::All(Vec::new())
+        };
+
+        Query {
+            fetch,
+            entities,
+            cursor: 0,
+            _marker: PhantomData
+        }
+    }
+
+    pub fn get(self, entity: Entity) -> Option<Q::Item<'w>> {
+        let (query_fetch, filter_fetch) = self.fetch.as_ref()?;
+
+        // Without a required component nothing below checks that it is alive
+        if let Candidates::All(entities) = &self.entities && !entities.contains(&entity) {
+            return None;
+        }
+
+        // SAFETY: the query borrows the world for 'w and is consumed here,
+        // so the returned item is the only one handed out.
+        unsafe {
+            if !F::matches(filter_fetch, entity) {
+                return None;
+            }
+            Q::fetch(query_fetch, entity)
+        }
+    }
+}
+
+impl<'w, Q: WorldQuery, F: QueryFilter> Iterator for Query<'w, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (query_fetch, filter_fetch) = self.fetch.as_ref()?;
+
+        while let Some(entity) = self.entities.get(self.cursor) {
+            self.cursor += 1;
+
+            // SAFETY: every entity of the snapshot is visited once, so mutable
+            // items never alias, and the world stays borrowed for 'w.
+            unsafe {
+                if !F::matches(filter_fetch, entity) {
+                    continue;
+                }
+                if let Some(item) = Q::fetch(query_fetch, entity) {
+                    return Some(item);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+    impl Component for Velocity {}
+
+    #[derive(Debug, PartialEq)]
+    struct Frozen;
+    impl Component for Frozen {}
+
+    fn spawn(world: &mut World, position: i32, velocity: Option<i32>) -> Entity {
+        let entity = world.new_entity();
+        world.add_component(entity, Position(position));
+        if let Some(velocity) = velocity {
+            world.add_component(entity, Velocity(velocity));
+        }
+        entity
+    }
+
+    #[test]
+    fn mutable_and_shared_components_together() {
+        let mut world = World::new();
+        let moving = spawn(&mut world, 0, Some(2));
+        let other = spawn(&mut world, 10, Some(-1));
+        let still = spawn(&mut world, 5, None);
+
+        let mut visited = 0;
+        for (mut position, velocity) in world.query::<(&mut Position, &Velocity)>() {
+            position.0 += velocity.0;
+            visited += 1;
+        }
+
+        assert_eq!(visited, 2);
+        assert_eq!(world.get_component::<Position>(moving), Some(&Position(2)));
+        assert_eq!(world.get_component::<Position>(other), Some(&Position(9)));
+        assert_eq!(world.get_component::<Position>(still), Some(&Position(5)));
+    }
+
+    #[test]
+    fn optional_component_does_not_filter() {
+        let mut world = World::new();
+        let moving = spawn(&mut world, 0, Some(2));
+        let still = spawn(&mut world, 5, None);
+
+        let mut items: Vec<(Entity, Option<i32>)> = world.query::<(Entity, &Position, Option<&Velocity>)>()
+            .map(|(entity, _, velocity)| (entity, velocity.map(|velocity| velocity.0)))
+            .collect();
+        items.sort_by_key(|(entity, _)| entity.index());
+        assert_eq!(items, [(moving, Some(2)), (still, None)]);
+
+        // Without any storage for the type, every entity gets None
+        assert_eq!(world.query::<(&Position, Option<&Frozen>)>().filter(|(_, frozen)| frozen.is_none()).count(), 2);
+    }
+
+    #[test]
+    fn with_and_without_filters() {
+        let mut world = World::new();
+        let frozen = spawn(&mut world, 0, Some(1));
+        let moving = spawn(&mut world, 1, Some(1));
+        let still = spawn(&mut world, 2, None);
+        world.add_component(frozen, Frozen);
+
+        let with: Vec<Entity> = world.query_filtered::<Entity, With<Frozen>>().collect();
+        assert_eq!(with, [frozen]);
+
+        let mut without: Vec<Entity> = world.query_filtered::<Entity, (With<Velocity>, Without<Frozen>)>().collect();
+        without.sort_by_key(|entity| entity.index());
+        assert_eq!(without, [moving]);
+
+        let mut neither: Vec<Entity> = world.query_filtered::<Entity, (Without<Velocity>, Without<Frozen>)>().collect();
+        neither.sort_by_key(|entity| entity.index());
+        assert_eq!(neither, [still]);
+    }
+
+    #[test]
+    fn get_on_missing_or_dead_entity() {
+        let mut world = World::new();
+        let moving = spawn(&mut world, 0, Some(1));
+        let still = spawn(&mut world, 2, None);
+        let dead = spawn(&mut world, 3, Some(1));
+        world.remove_entity(dead);
+        let reused = spawn(&mut world, 4, Some(1));
+        assert_eq!(reused.index(), dead.index());
+
+        assert_eq!(world.query::<&Velocity>().get(moving), Some(&Velocity(1)));
+        assert!(world.query::<&Velocity>().get(still).is_none());
+        assert!(world.query::<&Velocity>().get(dead).is_none());
+        assert!(world.query::<(&mut Position, &Velocity)>().get(dead).is_none());
+        assert!(world.query::<Entity>().get(dead).is_none());
+        assert!(world.query::<Option<&Velocity>>().get(dead).is_none());
+        assert_eq!(world.query::<Entity>().get(still), Some(still));
+        assert!(world.query_filtered::<Entity, With<Frozen>>().get(moving).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "mutably more than once")]
+    fn conflicting_access_panics() {
+        let mut world = World::new();
+        spawn(&mut world, 0, None);
+        world.query::<(&mut Position, &Position)>().count();
+    }
+
+    #[test]
+    #[should_panic(expected = "mutably more than once")]
+    fn conflicting_filter_panics() {
+        let mut world = World::new();
+        spawn(&mut world, 0, None);
+        world.query_filtered::<&mut Position, With<Position>>().count();
+    }
+
+    #[test]
+    fn smallest_storage_drives_iteration() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..4).map(|position| spawn(&mut world, position, None)).collect();
+        world.add_component(entities[3], Velocity(3));
+        world.add_component(entities[1], Velocity(1));
+
+        let query = world.query::<(Entity, &Position, &Velocity)>();
+        match &query.entities {
+            Candidates::Storage(driver) => assert_eq!(unsafe { (&**driver).len() }, 2),
+            Candidates::All(_) => panic!("a required storage should drive the query")
+        }
+
+        // Walked in the dense order of the velocities
+        let order: Vec<Entity> = query.map(|(entity, _, _)| entity).collect();
+        assert_eq!(order, [entities[3], entities[1]]);
+    }
+}
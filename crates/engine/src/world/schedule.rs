@@ -0,0 +1,686 @@
+use std::fmt;
+
+use crate::core::frame_context::FrameContext;
+use crate::glutils::shader::Shader;
+use crate::world::commands::Commands;
+use crate::world::query::Access;
+use crate::world::system::System;
+use crate::world::world::World;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    PreUpdate,
+    // Runs `System::fixed_update` zero or more times per frame, at the fixed
+    // update rate set on `Time`.
+    FixedUpdate,
+    Update,
+    PostUpdate,
+    Render
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [Stage::PreUpdate, Stage::FixedUpdate, Stage::Update, Stage::PostUpdate, Stage::Render];
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Stage::PreUpdate => "PreUpdate",
+            Stage::FixedUpdate => "FixedUpdate",
+            Stage::Update => "Update",
+            Stage::PostUpdate => "PostUpdate",
+            Stage::Render => "Render"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutorKind {
+    // Every system runs on the main thread, in resolved order.
+    SingleThreaded,
+    // Consecutive systems with compatible declared access run together on the
+    // rayon thread pool. Render systems always stay on the main thread.
+    MultiThreaded
+}
+
+pub type RunCondition = Box<dyn Fn(&World) -> bool>;
+
+// `System::update` or `System::fixed_update`, depending on the stage.
+type RunFn = fn(&mut dyn System, &mut FrameContext);
+
+// A system together with its scheduling constraints, built with the
+// `IntoSystemConfig` methods before being handed to `Schedule::add_system`.
+pub struct SystemConfig {
+    name: String,
+    system: Box<dyn System>,
+    labels: Vec<String>,
+    before: Vec<String>,
+    after: Vec<String>,
+    conditions: Vec<RunCondition>,
+    // World change tick of the previous run, for `Added`/`Changed` filters.
+    last_run: u64
+}
+
+impl SystemConfig {
+    pub fn new<S: System + 'static>(system: S) -> Self {
+        let type_name = std::any::type_name::<S>();
+        SystemConfig {
+            name: type_name.rsplit("::").next().unwrap_or(type_name).to_string(),
+            system: Box::new(system),
+            labels: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            conditions: Vec::new(),
+            last_run: 0
+        }
+    }
+}
+
+pub trait IntoSystemConfig {
+    fn into_config(self) -> SystemConfig;
+
+    fn label(self, label: &str) -> SystemConfig where Self: Sized {
+        let mut config = self.into_config();
+        config.labels.push(label.to_string());
+        config
+    }
+
+    fn before(self, label: &str) -> SystemConfig where Self: Sized {
+        let mut config = self.into_config();
+        config.before.push(label.to_string());
+        config
+    }
+
+    fn after(self, label: &str) -> SystemConfig where Self: Sized {
+        let mut config = self.into_config();
+        config.after.push(label.to_string());
+        config
+    }
+
+    fn run_if<F: Fn(&World) -> bool + 'static>(self, condition: F) -> SystemConfig where Self: Sized {
+        let mut config = self.into_config();
+        config.conditions.push(Box::new(condition));
+        config
+    }
+}
+
+impl IntoSystemConfig for SystemConfig {
+    fn into_config(self) -> SystemConfig {
+        self
+    }
+}
+
+impl<S: System + 'static> IntoSystemConfig for S {
+    fn into_config(self) -> SystemConfig {
+        SystemConfig::new(self)
+    }
+}
+
+#[derive(Debug)]
+pub enum ScheduleError {
+    // The `before`/`after` constraints of these systems form a cycle.
+    Cycle { stage: Stage, systems: Vec<String> }
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::Cycle { stage, systems } => {
+                write!(f, "ordering cycle in stage {} between: {}", stage.to_str(), systems.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+struct StageSystems {
+    systems: Vec<SystemConfig>,
+    // Indices into `systems`, in execution order once the schedule is built.
+    order: Vec<usize>,
+    // `order` cut into groups of systems that may run at the same time.
+    batches: Vec<Vec<usize>>,
+    accesses: Vec<Option<Access>>
+}
+
+// Runs systems stage by stage. Within a stage, systems keep the order they
+// were added in unless `before`/`after` constraints say otherwise.
+pub struct Schedule {
+    stages: Vec<StageSystems>,
+    executor: ExecutorKind,
+    built: bool
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::new()
+    }
+}
+
+#[allow(dead_code)]
+impl Schedule {
+    pub fn new() -> Self {
+        Schedule {
+            stages: Stage::ALL.iter().map(|_| StageSystems {
+                systems: Vec::new(),
+                order: Vec::new(),
+                batches: Vec::new(),
+                accesses: Vec::new()
+            }).collect(),
+            executor: ExecutorKind::MultiThreaded,
+            built: false
+        }
+    }
+
+    pub fn set_executor(&mut self, executor: ExecutorKind) -> &mut Self {
+        self.executor = executor;
+        self
+    }
+
+    pub fn executor(&self) -> ExecutorKind {
+        self.executor
+    }
+
+    pub fn add_system<S: IntoSystemConfig>(&mut self, stage: Stage, system: S) -> &mut Self {
+        self.stages[stage as usize].systems.push(system.into_config());
+        self.built = false;
+        self
+    }
+
+    pub fn is_built(&self) -> bool {
+        self.built
+    }
+
+    // Resolves the execution order of every stage.
+    pub fn build(&mut self) -> Result<(), ScheduleError> {
+        for stage in Stage::ALL {
+            let (order, predecessors) = self.resolve_stage(stage)?;
+
+            let stage_systems = &mut self.stages[stage as usize];
+            stage_systems.accesses = stage_systems.systems.iter().map(|config| config.system.access()).collect();
+            stage_systems.batches = batch(&order, &predecessors, &stage_systems.accesses);
+            stage_systems.order = order;
+        }
+        self.built = true;
+        Ok(())
+    }
+
+    // Returns the execution order and, for each system, the systems that must run before it.
+    fn resolve_stage(&self, stage: Stage) -> Result<(Vec<usize>, Vec<Vec<usize>>), ScheduleError> {
+        let systems = &self.stages[stage as usize].systems;
+        let count = systems.len();
+
+        let mut successors: Vec<Vec<usize>> = vec![Vec::new(); count];
+        let mut in_degree = vec![0usize; count];
+
+        let mut add_edge = |from: usize, to: usize| {
+            if from != to && !successors[from].contains(&to) {
+                successors[from].push(to);
+                in_degree[to] += 1;
+            }
+        };
+
+        for (index, system) in systems.iter().enumerate() {
+            for label in system.before.iter() {
+                for target in self.labelled(stage, label) {
+                    add_edge(index, target);
+                }
+            }
+            for label in system.after.iter() {
+                for target in self.labelled(stage, label) {
+                    add_edge(target, index);
+                }
+            }
+        }
+
+        // Kahn's algorithm, always picking the earliest added ready system so
+        // unconstrained systems stay in insertion order.
+        let mut order = Vec::with_capacity(count);
+        let mut done = vec![false; count];
+        while order.len() < count {
+            let Some(next) = (0..count).find(|&i| !done[i] && in_degree[i] == 0) else {
+                // Whatever is left waits on a cycle; trim the systems that only
+                // come after it so the error names the cycle itself.
+                let mut remaining: Vec<usize> = (0..count).filter(|&i| !done[i]).collect();
+                while let Some(position) = remaining.iter()
+                    .position(|&i| !successors[i].iter().any(|s| remaining.contains(s))) {
+                    remaining.remove(position);
+                }
+
+                let systems = remaining.iter()
+                    .map(|&i| systems[i].name.clone())
+                    .collect();
+                return Err(ScheduleError::Cycle { stage, systems });
+            };
+
+            done[next] = true;
+            order.push(next);
+            for &successor in successors[next].iter() {
+                in_degree[successor] -= 1;
+            }
+        }
+
+        let mut predecessors = vec![Vec::new(); count];
+        for (index, targets) in successors.iter().enumerate() {
+            for &target in targets {
+                predecessors[target].push(index);
+            }
+        }
+
+        Ok((order, predecessors))
+    }
+
+    fn labelled(&self, stage: Stage, label: &str) -> Vec<usize> {
+        let matches: Vec<usize> = self.stages[stage as usize].systems.iter()
+            .enumerate()
+            .filter(|(_, system)| system.labels.iter().any(|l| l == label))
+            .map(|(index, _)| index)
+            .collect();
+
+        if matches.is_empty() && !self.has_label(label) {
+            eprintln!("Schedule: label '{}' referenced in stage {} is not used by any system", label, stage.to_str());
+        }
+
+        matches
+    }
+
+    fn has_label(&self, label: &str) -> bool {
+        self.stages.iter()
+            .flat_map(|stage| stage.systems.iter())
+            .any(|system| system.labels.iter().any(|l| l == label))
+    }
+
+    // Names of the systems of each stage, in the order they will run.
+    pub fn resolved_order(&self) -> Vec<(Stage, Vec<String>)> {
+        Stage::ALL.iter()
+            .map(|&stage| {
+                let stage_systems = &self.stages[stage as usize];
+                let names = stage_systems.order.iter()
+                    .map(|&index| stage_systems.systems[index].name.clone())
+                    .collect();
+                (stage, names)
+            })
+            .collect()
+    }
+
+    // One line per stage; systems that may run in parallel are grouped as `[A | B]`.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for stage in Stage::ALL {
+            let stage_systems = &self.stages[stage as usize];
+            let steps: Vec<String> = if self.runs_in_parallel(stage) {
+                stage_systems.batches.iter().map(|batch| {
+                    let names: Vec<&str> = batch.iter().map(|&index| stage_systems.systems[index].name.as_str()).collect();
+                    if names.len() > 1 { format!("[{}]", names.join(" | ")) } else { names.join("") }
+                }).collect()
+            } else {
+                stage_systems.order.iter().map(|&index| stage_systems.systems[index].name.clone()).collect()
+            };
+            report.push_str(&format!("{}: {}\n", stage.to_str(), steps.join(" -> ")));
+        }
+        report
+    }
+
+    fn runs_in_parallel(&self, stage: Stage) -> bool {
+        self.executor == ExecutorKind::MultiThreaded && stage != Stage::Render
+    }
+
+    // Runs one pass of the stage: `fixed_update` for `Stage::FixedUpdate`, `update` otherwise.
+    pub fn run_stage(&mut self, stage: Stage, ctx: &mut FrameContext) {
+        let run: RunFn = if stage == Stage::FixedUpdate {
+            |system, ctx| system.fixed_update(ctx)
+        } else {
+            |system, ctx| system.update(ctx)
+        };
+
+        if !self.runs_in_parallel(stage) {
+            let stage_systems = &mut self.stages[stage as usize];
+            for &index in stage_systems.order.iter() {
+                let config = &mut stage_systems.systems[index];
+                if !config.conditions.iter().all(|condition| condition(ctx.world)) {
+                    continue;
+                }
+
+                config.last_run = ctx.world.begin_system_run(config.last_run);
+                run(config.system.as_mut(), ctx);
+                ctx.apply_commands();
+                ctx.world.end_system_run();
+            }
+            return;
+        }
+
+        let stage_systems = &mut self.stages[stage as usize];
+        for batch in stage_systems.batches.iter() {
+            let runnable: Vec<usize> = batch.iter()
+                .copied()
+                .filter(|&index| stage_systems.systems[index].conditions.iter().all(|condition| condition(ctx.world)))
+                .collect();
+
+            if let [index] = runnable[..] {
+                let config = &mut stage_systems.systems[index];
+                config.last_run = ctx.world.begin_system_run(config.last_run);
+                run(config.system.as_mut(), ctx);
+                ctx.apply_commands();
+                ctx.world.end_system_run();
+            } else if !runnable.is_empty() {
+                run_parallel(&mut stage_systems.systems, &stage_systems.accesses, &runnable, run, ctx);
+            }
+        }
+    }
+
+    pub fn run_render(&mut self, ctx: &mut FrameContext, shader: &Shader) {
+        let stage_systems = &mut self.stages[Stage::Render as usize];
+        for &index in stage_systems.order.iter() {
+            let config = &mut stage_systems.systems[index];
+            if !config.conditions.iter().all(|condition| condition(ctx.world)) {
+                continue;
+            }
+
+            config.last_run = ctx.world.begin_system_run(config.last_run);
+            config.system.render(ctx, shader);
+            ctx.apply_commands();
+            ctx.world.end_system_run();
+        }
+    }
+}
+
+// Cuts the resolved order into consecutive groups whose members all declare
+// their access, do not conflict with each other and are not ordered relative
+// to each other.
+fn batch(order: &[usize], predecessors: &[Vec<usize>], accesses: &[Option<Access>]) -> Vec<Vec<usize>> {
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+
+    for &index in order.iter() {
+        let joins_last = match (batches.last(), &accesses[index]) {
+            (Some(last), Some(access)) => last.iter().all(|&other| {
+                !predecessors[index].contains(&other)
+                    && accesses[other].as_ref().is_some_and(|other_access| other_access.is_compatible(access))
+            }),
+            _ => false
+        };
+
+        if joins_last {
+            batches.last_mut().unwrap().push(index);
+        } else {
+            batches.push(vec![index]);
+        }
+    }
+
+    batches
+}
+
+// Runs each system of the batch on the thread pool, each one on a view of the
+// world that owns what the system writes. Commands are applied afterwards in
+// schedule order so the outcome does not depend on timing; only the ids of
+// entities spawned by the batch do, as they are reserved while it runs.
+fn run_parallel(systems: &mut [SystemConfig], accesses: &[Option<Access>], batch: &[usize], run: RunFn, ctx: &mut FrameContext) {
+    let batch_accesses: Vec<&Access> = batch.iter()
+        .map(|&index| accesses[index].as_ref().expect("Parallel systems declare their access"))
+        .collect();
+
+    let mut batch_configs: Vec<&mut SystemConfig> = systems.iter_mut()
+        .enumerate()
+        .filter(|(index, _)| batch.contains(index))
+        .map(|(_, config)| config)
+        .collect();
+
+    // Every system still gets its own tick, as if they had run one after another.
+    let run_ticks: Vec<(u64, u64)> = batch_configs.iter_mut().map(|config| {
+        let last_run = config.last_run;
+        config.last_run = ctx.world.begin_system_run(last_run);
+        (last_run, config.last_run)
+    }).collect();
+
+    let mut views = ctx.world.split(&batch_accesses);
+    for (view, (last_run, this_run)) in views.iter_mut().zip(run_ticks) {
+        view.set_run_ticks(last_run, this_run);
+    }
+    let mut commands: Vec<Commands> = batch.iter().map(|_| Commands::new(ctx.world)).collect();
+
+    let batch_systems: Vec<&mut Box<dyn System>> = batch_configs.into_iter()
+        .map(|config| &mut config.system)
+        .collect();
+
+    let time = ctx.time;
+    let input = ctx.input;
+    rayon::scope(|scope| {
+        for ((system, view), commands) in batch_systems.into_iter().zip(views.iter_mut()).zip(commands.iter_mut()) {
+            scope.spawn(move |_| {
+                let mut view_ctx = FrameContext { time, input, world: view, commands };
+                run(system.as_mut(), &mut view_ctx);
+            });
+        }
+    });
+
+    for view in views {
+        ctx.world.merge(view);
+    }
+    for mut system_commands in commands {
+        system_commands.apply(ctx.world);
+    }
+    ctx.world.end_system_run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::input::InputHandler;
+    use crate::core::time::Time;
+
+    struct Log(Vec<&'static str>);
+    struct Left(u32);
+    struct Right(u32);
+    struct Total(u32);
+
+    // Appends its name to the `Log` resource.
+    struct Logger(&'static str);
+
+    impl System for Logger {
+        fn update(&mut self, ctx: &mut FrameContext) {
+            ctx.world.resource_mut::<Log>().unwrap().0.push(self.0);
+        }
+    }
+
+    // Declares the access built by its function, does nothing.
+    struct Declared(fn() -> Access);
+
+    impl System for Declared {
+        fn access(&self) -> Option<Access> {
+            Some((self.0)())
+        }
+    }
+
+    fn reads_left() -> Access {
+        let mut access = Access::new();
+        access.add_read::<Left>();
+        access
+    }
+
+    fn reads_right() -> Access {
+        let mut access = Access::new();
+        access.add_read::<Right>();
+        access
+    }
+
+    struct AddLeft;
+
+    impl System for AddLeft {
+        fn access(&self) -> Option<Access> {
+            let mut access = Access::new();
+            access.add_write::<Left>();
+            Some(access)
+        }
+
+        fn update(&mut self, ctx: &mut FrameContext) {
+            ctx.world.resource_mut::<Left>().unwrap().0 += 1;
+        }
+    }
+
+    struct AddRight;
+
+    impl System for AddRight {
+        fn access(&self) -> Option<Access> {
+            let mut access = Access::new();
+            access.add_write::<Right>();
+            Some(access)
+        }
+
+        fn update(&mut self, ctx: &mut FrameContext) {
+            ctx.world.resource_mut::<Right>().unwrap().0 += 2;
+        }
+    }
+
+    struct Sum;
+
+    impl System for Sum {
+        fn access(&self) -> Option<Access> {
+            let mut access = Access::new();
+            access.add_read::<Left>();
+            access.add_read::<Right>();
+            access.add_write::<Total>();
+            Some(access)
+        }
+
+        fn update(&mut self, ctx: &mut FrameContext) {
+            let total = ctx.world.resource::<Left>().unwrap().0 + ctx.world.resource::<Right>().unwrap().0;
+            ctx.world.resource_mut::<Total>().unwrap().0 = total;
+        }
+    }
+
+    fn run_update(schedule: &mut Schedule, world: &mut World) {
+        let time = Time::new();
+        let input = InputHandler::new();
+        let mut commands = Commands::new(world);
+        let mut ctx = FrameContext { time: &time, input: &input, world, commands: &mut commands };
+        schedule.run_stage(Stage::Update, &mut ctx);
+    }
+
+    fn batch_names(schedule: &Schedule, stage: Stage) -> Vec<Vec<String>> {
+        let stage_systems = &schedule.stages[stage as usize];
+        stage_systems.batches.iter()
+            .map(|batch| batch.iter().map(|&index| stage_systems.systems[index].name.clone()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn single_threaded_runs_in_resolved_order() {
+        let mut schedule = Schedule::new();
+        schedule.set_executor(ExecutorKind::SingleThreaded);
+        schedule.add_system(Stage::Update, Logger("a").label("a"));
+        schedule.add_system(Stage::Update, Logger("b").after("c"));
+        schedule.add_system(Stage::Update, Logger("c").label("c"));
+        schedule.add_system(Stage::Update, Logger("d").before("a"));
+        schedule.build().unwrap();
+
+        let mut world = World::new();
+        world.insert_resource(Log(Vec::new()));
+        for _ in 0..3 {
+            run_update(&mut schedule, &mut world);
+        }
+
+        let log = &world.resource::<Log>().unwrap().0;
+        assert_eq!(log[..4], ["c", "b", "d", "a"]);
+        assert_eq!(log[4..8], log[..4]);
+        assert_eq!(log[8..], log[..4]);
+    }
+
+    #[test]
+    fn ordering_cycle_is_reported() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, Logger("a").label("a").after("b"));
+        schedule.add_system(Stage::Update, Logger("b").label("b").after("a"));
+        schedule.add_system(Stage::Update, Logger("c").after("b"));
+
+        match schedule.build() {
+            Err(ScheduleError::Cycle { stage, systems }) => {
+                assert_eq!(stage, Stage::Update);
+                assert_eq!(systems, ["Logger", "Logger"]);
+            }
+            Ok(()) => panic!("the cycle was not detected")
+        }
+    }
+
+    #[test]
+    fn access_conflicts() {
+        let mut reads = Access::new();
+        reads.add_read::<Left>();
+        let mut other_reads = Access::new();
+        other_reads.add_read::<Left>();
+        other_reads.add_read::<Right>();
+        let mut writes = Access::new();
+        writes.add_write::<Left>();
+        let mut unrelated = Access::new();
+        unrelated.add_write::<Right>();
+
+        assert!(reads.is_compatible(&other_reads));
+        assert!(!reads.is_compatible(&writes));
+        assert!(!writes.is_compatible(&reads));
+        assert!(!writes.is_compatible(&writes));
+        assert!(writes.is_compatible(&reads_only_total()));
+        assert!(!unrelated.is_compatible(&other_reads));
+
+        assert_eq!(other_reads.find_conflict(), None);
+        writes.add_read::<Left>();
+        assert_eq!(writes.find_conflict(), Some(std::any::type_name::<Left>()));
+    }
+
+    fn reads_only_total() -> Access {
+        let mut access = Access::new();
+        access.add_read::<Total>();
+        access
+    }
+
+    #[test]
+    fn batches_group_compatible_unordered_systems() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, AddLeft);
+        schedule.add_system(Stage::Update, AddRight.label("right"));
+        // Reads what both previous systems write
+        schedule.add_system(Stage::Update, Sum);
+        schedule.add_system(Stage::Update, Declared(reads_left));
+        // Compatible, but ordered after the previous one
+        schedule.add_system(Stage::Update, Declared(reads_right).label("last"));
+        schedule.add_system(Stage::Update, AddLeft.after("last"));
+        // Exclusive: no declared access
+        schedule.add_system(Stage::Update, Logger("exclusive"));
+        schedule.build().unwrap();
+
+        assert_eq!(batch_names(&schedule, Stage::Update), vec![
+            vec!["AddLeft".to_string(), "AddRight".to_string()],
+            vec!["Sum".to_string(), "Declared".to_string(), "Declared".to_string()],
+            vec!["AddLeft".to_string()],
+            vec!["Logger".to_string()]
+        ]);
+    }
+
+    #[test]
+    fn ordering_constraint_splits_batch() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, AddLeft.label("left"));
+        schedule.add_system(Stage::Update, AddRight.after("left"));
+        schedule.build().unwrap();
+
+        assert_eq!(batch_names(&schedule, Stage::Update), vec![vec!["AddLeft".to_string()], vec!["AddRight".to_string()]]);
+    }
+
+    #[test]
+    fn parallel_batches_run_before_the_next_one() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, AddLeft);
+        schedule.add_system(Stage::Update, AddRight);
+        schedule.add_system(Stage::Update, Sum);
+        schedule.build().unwrap();
+        assert_eq!(schedule.report().lines().nth(2), Some("Update: [AddLeft | AddRight] -> Sum"));
+
+        let mut world = World::new();
+        world.insert_resource(Left(0));
+        world.insert_resource(Right(0));
+        world.insert_resource(Total(0));
+        for _ in 0..10 {
+            run_update(&mut schedule, &mut world);
+        }
+
+        // Resources moved into the parallel views are back in the world
+        assert_eq!(world.resource::<Left>().unwrap().0, 10);
+        assert_eq!(world.resource::<Right>().unwrap().0, 20);
+        assert_eq!(world.resource::<Total>().unwrap().0, 30);
+    }
+}
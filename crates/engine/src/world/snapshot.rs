@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use hashbrown::HashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::world::components::Component;
+use crate::world::entity::Entity;
+use crate::world::world::World;
+
+// Old-to-new entity ids built while loading a snapshot.
+#[derive(Debug, Clone)]
+pub struct EntityMap {
+    entities: HashMap<Entity, Entity>
+}
+
+impl Default for EntityMap {
+    fn default() -> Self {
+        EntityMap::new()
+    }
+}
+
+impl EntityMap {
+    pub fn new() -> Self {
+        EntityMap {
+            entities: HashMap::new()
+        }
+    }
+
+    pub fn insert(&mut self, old: Entity, new: Entity) {
+        self.entities.insert(old, new);
+    }
+
+    pub fn get(&self, old: Entity) -> Option<Entity> {
+        self.entities.get(&old).copied()
+    }
+
+    // Fails for entities that were not part of the snapshot: keeping their old
+    // id could point at an unrelated entity of the world.
+    pub fn map(&self, old: Entity) -> Result<Entity, String> {
+        self.get(old).ok_or_else(|| format!("{:?} is referenced but not part of the snapshot", old))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.entities.iter().map(|(old, new)| (*old, *new))
+    }
+}
+
+// Implemented by components that hold entity ids, so they can be rewritten
+// to the ids allocated when a snapshot is loaded.
+pub trait MapEntities {
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    // Human readable, stable output suited to golden files.
+    Json,
+    // Compact bincode encoding.
+    Binary
+}
+
+type SaveFn<P> = Box<dyn Fn(&World) -> Result<Vec<(Entity, P)>, String> + Send + Sync>;
+type LoadFn<P> = Box<dyn Fn(&mut World, Vec<(Entity, P)>, &EntityMap) -> Result<(), String> + Send + Sync>;
+type MapFn<T> = fn(&mut T, &EntityMap) -> Result<(), String>;
+
+struct SnapshotComponent {
+    save_json: SaveFn<Value>,
+    load_json: LoadFn<Value>,
+    save_binary: SaveFn<Vec<u8>>,
+    load_binary: LoadFn<Vec<u8>>
+}
+
+// Components that take part in snapshots, by the name they are saved under.
+// Components that are not registered are skipped when saving.
+pub struct SnapshotRegistry {
+    components: BTreeMap<String, SnapshotComponent>
+}
+
+impl Default for SnapshotRegistry {
+    fn default() -> Self {
+        SnapshotRegistry::new()
+    }
+}
+
+#[allow(dead_code)]
+impl SnapshotRegistry {
+    pub fn new() -> Self {
+        SnapshotRegistry {
+            components: BTreeMap::new()
+        }
+    }
+
+    pub fn register<T>(&mut self, name: &str)
+    where
+        T: Component + Serialize + DeserializeOwned + 'static + Send + Sync
+    {
+        self.register_component::<T>(name, None);
+    }
+
+    // For components holding entity ids, which are remapped on load.
+    pub fn register_mapped<T>(&mut self, name: &str)
+    where
+        T: Component + Serialize + DeserializeOwned + MapEntities + 'static + Send + Sync
+    {
+        self.register_component::<T>(name, Some(<T as MapEntities>::map_entities));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.components.contains_key(name)
+    }
+
+    fn register_component<T>(&mut self, name: &str, map: Option<MapFn<T>>)
+    where
+        T: Component + Serialize + DeserializeOwned + 'static + Send + Sync
+    {
+        let component = SnapshotComponent {
+            save_json: Box::new(|world| save_components::<T, _>(world, |component| {
+                serde_json::to_value(component).map_err(|e| e.to_string())
+            })),
+            load_json: Box::new(move |world, saved, entity_map| load_components::<T, _>(world, saved, entity_map, map, |value| {
+                serde_json::from_value(value).map_err(|e| e.to_string())
+            })),
+            save_binary: Box::new(|world| save_components::<T, _>(world, |component| {
+                bincode::serialize(component).map_err(|e| e.to_string())
+            })),
+            load_binary: Box::new(move |world, saved, entity_map| load_components::<T, _>(world, saved, entity_map, map, |bytes| {
+                bincode::deserialize(&bytes).map_err(|e| e.to_string())
+            }))
+        };
+
+        if self.components.insert(name.to_string(), component).is_some() {
+            eprintln!("Component '{}' was already registered for snapshots, replacing it", name);
+        }
+    }
+}
+
+fn save_components<T: Component + 'static, P>(world: &World, encode: impl Fn(&T) -> Result<P, String>) -> Result<Vec<(Entity, P)>, String> {
+    let mut saved = Vec::new();
+    if let Some(components) = world.get_components::<T>() {
+        for (entity, component) in components.iter() {
+            saved.push((entity, encode(component)?));
+        }
+    }
+
+    saved.sort_by_key(|(entity, _)| entity.index());
+    Ok(saved)
+}
+
+fn load_components<T: Component + 'static + Send + Sync, P>(
+    world: &mut World,
+    saved: Vec<(Entity, P)>,
+    entity_map: &EntityMap,
+    map: Option<MapFn<T>>,
+    decode: impl Fn(P) -> Result<T, String>
+) -> Result<(), String> {
+    for (entity, payload) in saved {
+        let new_entity = entity_map.get(entity)
+            .ok_or_else(|| format!("{:?} is not listed in the snapshot entities", entity))?;
+
+        let mut component = decode(payload)?;
+        if let Some(map) = map {
+            map(&mut component, entity_map)?;
+        }
+        world.add_component(new_entity, component);
+    }
+
+    Ok(())
+}
+
+// `P` is the encoded form of a single component: a JSON value, or bincode bytes.
+#[derive(Serialize, Deserialize)]
+struct Snapshot<P> {
+    entities: Vec<Entity>,
+    components: BTreeMap<String, Vec<(Entity, P)>>
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    MissingRegistry,
+    UnknownComponent(String),
+    InvalidComponent { component: String, message: String },
+    Json(serde_json::Error),
+    Binary(bincode::Error)
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::MissingRegistry => write!(f, "the world has no SnapshotRegistry resource"),
+            SnapshotError::UnknownComponent(name) => write!(f, "snapshot contains unregistered component '{}'", name),
+            SnapshotError::InvalidComponent { component, message } => {
+                write!(f, "component '{}' could not be restored: {}", component, message)
+            }
+            SnapshotError::Json(e) => write!(f, "invalid JSON snapshot: {}", e),
+            SnapshotError::Binary(e) => write!(f, "invalid binary snapshot: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl World {
+    // Saves every alive entity and its registered components.
+    pub fn save_snapshot(&self, format: SnapshotFormat) -> Result<Vec<u8>, SnapshotError> {
+        let registry = self.resource::<SnapshotRegistry>().ok_or(SnapshotError::MissingRegistry)?;
+        let entities: Vec<Entity> = self.entities().collect();
+
+        match format {
+            SnapshotFormat::Json => {
+                let snapshot = Snapshot {
+                    entities,
+                    components: save_all(self, registry, |component| &component.save_json)?
+                };
+                serde_json::to_vec_pretty(&snapshot).map_err(SnapshotError::Json)
+            }
+            SnapshotFormat::Binary => {
+                let snapshot = Snapshot {
+                    entities,
+                    components: save_all(self, registry, |component| &component.save_binary)?
+                };
+                bincode::serialize(&snapshot).map_err(SnapshotError::Binary)
+            }
+        }
+    }
+
+    // Spawns the snapshot's entities next to the existing ones and returns the
+    // ids they were given. Nothing is spawned if the snapshot cannot be restored.
+    pub fn load_snapshot(&mut self, data: &[u8], format: SnapshotFormat) -> Result<EntityMap, SnapshotError> {
+        self.resource_scope(|world, registry: &mut SnapshotRegistry| {
+            match format {
+                SnapshotFormat::Json => {
+                    let snapshot: Snapshot<Value> = serde_json::from_slice(data).map_err(SnapshotError::Json)?;
+                    load_all(world, registry, snapshot, |component| &component.load_json)
+                }
+                SnapshotFormat::Binary => {
+                    let snapshot: Snapshot<Vec<u8>> = bincode::deserialize(data).map_err(SnapshotError::Binary)?;
+                    load_all(world, registry, snapshot, |component| &component.load_binary)
+                }
+            }
+        })
+        .unwrap_or(Err(SnapshotError::MissingRegistry))
+    }
+}
+
+fn save_all<P>(
+    world: &World,
+    registry: &SnapshotRegistry,
+    save: impl Fn(&SnapshotComponent) -> &SaveFn<P>
+) -> Result<BTreeMap<String, Vec<(Entity, P)>>, SnapshotError> {
+    let mut components = BTreeMap::new();
+    for (name, component) in &registry.components {
+        let saved = save(component)(world)
+            .map_err(|message| SnapshotError::InvalidComponent { component: name.clone(), message })?;
+
+        if !saved.is_empty() {
+            components.insert(name.clone(), saved);
+        }
+    }
+
+    Ok(components)
+}
+
+fn load_all<P>(
+    world: &mut World,
+    registry: &SnapshotRegistry,
+    snapshot: Snapshot<P>,
+    load: impl Fn(&SnapshotComponent) -> &LoadFn<P>
+) -> Result<EntityMap, SnapshotError> {
+    if let Some(name) = snapshot.components.keys().find(|name| !registry.contains(name)) {
+        return Err(SnapshotError::UnknownComponent(name.clone()));
+    }
+
+    let mut entity_map = EntityMap::new();
+    for entity in snapshot.entities {
+        entity_map.insert(entity, world.new_entity());
+    }
+
+    for (name, saved) in snapshot.components {
+        if let Err(message) = load(&registry.components[&name])(world, saved, &entity_map) {
+            for (_, entity) in entity_map.iter() {
+                world.remove_entity(entity);
+            }
+            return Err(SnapshotError::InvalidComponent { component: name, message });
+        }
+    }
+
+    Ok(entity_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+    use crate::world::components::{Children, Parent, TransformComponent};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    fn world_with_registry() -> World {
+        let mut world = World::new();
+        let mut registry = SnapshotRegistry::new();
+        registry.register::<Health>("health");
+        registry.register::<TransformComponent>("transform");
+        registry.register_mapped::<Parent>("parent");
+        registry.register_mapped::<Children>("children");
+        world.insert_resource(registry);
+        world
+    }
+
+    fn round_trip(format: SnapshotFormat) {
+        let mut source = world_with_registry();
+        let root = source.new_entity();
+        let child = source.new_entity();
+        let removed = source.new_entity();
+        source.remove_entity(removed);
+        // Reuses the index of `removed`, with a new generation
+        let grandchild = source.new_entity();
+
+        source.add_component(root, Health(3));
+        let mut transform = TransformComponent::new();
+        transform.transform.set_local_position(Vector3::new(1.0, 2.0, 3.0));
+        source.add_component(child, transform);
+        source.set_parent(child, root);
+        source.set_parent(grandchild, child);
+
+        let data = source.save_snapshot(format).unwrap();
+
+        // Loaded next to existing entities, so every id has to be remapped
+        let mut target = world_with_registry();
+        let existing: Vec<Entity> = (0..5).map(|_| target.new_entity()).collect();
+        let entity_map = target.load_snapshot(&data, format).unwrap();
+
+        assert_eq!(entity_map.len(), 3);
+        assert_eq!(target.entity_count(), 8);
+        let [new_root, new_child, new_grandchild] = [root, child, grandchild].map(|old| entity_map.get(old).unwrap());
+        assert!(!existing.contains(&new_root));
+
+        assert_eq!(target.get_component::<Health>(new_root), Some(&Health(3)));
+        assert_eq!(
+            *target.get_component::<TransformComponent>(new_child).unwrap().transform.get_local_position(),
+            Vector3::new(1.0, 2.0, 3.0)
+        );
+        assert_eq!(target.get_component::<Parent>(new_child).map(|parent| parent.get()), Some(new_root));
+        assert_eq!(target.get_component::<Parent>(new_grandchild).map(|parent| parent.get()), Some(new_child));
+        let children: Vec<Entity> = target.get_component::<Children>(new_root).unwrap().iter().collect();
+        assert_eq!(children, vec![new_child]);
+
+        // Saving the loaded entities again gives the same snapshot, up to ids
+        for entity in existing {
+            target.remove_entity(entity);
+        }
+        let reloaded = world_with_registry().load_snapshot(&target.save_snapshot(format).unwrap(), format).unwrap();
+        assert_eq!(reloaded.len(), 3);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        round_trip(SnapshotFormat::Json);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        round_trip(SnapshotFormat::Binary);
+    }
+
+    #[test]
+    fn json_output_is_stable() {
+        let mut world = world_with_registry();
+        for health in [5, 8] {
+            let entity = world.new_entity();
+            world.add_component(entity, Health(health));
+        }
+
+        let first = world.save_snapshot(SnapshotFormat::Json).unwrap();
+        let second = world.save_snapshot(SnapshotFormat::Json).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn dangling_reference_is_refused() {
+        let data = br#"{
+            "entities": [{ "index": 0, "generation": 0 }],
+            "components": {
+                "parent": [[{ "index": 0, "generation": 0 }, { "index": 7, "generation": 0 }]]
+            }
+        }"#;
+
+        let mut world = world_with_registry();
+        // Would be aliased by the dangling reference if it were kept as is
+        for _ in 0..8 {
+            world.new_entity();
+        }
+
+        match world.load_snapshot(data, SnapshotFormat::Json) {
+            Err(SnapshotError::InvalidComponent { component, .. }) => assert_eq!(component, "parent"),
+            other => panic!("expected an invalid parent, got {:?}", other.map(|entity_map| entity_map.len()))
+        }
+        assert_eq!(world.entity_count(), 8);
+    }
+
+    #[test]
+    fn unknown_component_is_refused() {
+        let data = br#"{ "entities": [], "components": { "mana": [] } }"#;
+        let mut world = world_with_registry();
+        assert!(matches!(world.load_snapshot(data, SnapshotFormat::Json), Err(SnapshotError::UnknownComponent(name)) if name == "mana"));
+    }
+}
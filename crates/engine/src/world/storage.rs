@@ -0,0 +1,191 @@
+use crate::world::change_detection::ComponentTicks;
+use crate::world::entity::Entity;
+
+const EMPTY: u32 = u32::MAX;
+
+// Dense component storage: `data[i]` belongs to `entities[i]`, and `sparse`
+// maps an entity index to its position in the dense arrays. Iteration walks
+// contiguous memory and lookups are two array reads instead of a hash.
+#[derive(Debug)]
+pub struct SparseSet<T> {
+    sparse: Vec<u32>,
+    entities: Vec<Entity>,
+    data: Vec<T>,
+    ticks: Vec<ComponentTicks>
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        SparseSet::new()
+    }
+}
+
+#[allow(dead_code)]
+impl<T> SparseSet<T> {
+    pub fn new() -> Self {
+        SparseSet {
+            sparse: Vec::new(),
+            entities: Vec::new(),
+            data: Vec::new(),
+            ticks: Vec::new()
+        }
+    }
+
+    // `tick` is the world change tick the value is stamped with.
+    pub fn insert(&mut self, entity: Entity, value: T, tick: u64) -> Option<T> {
+        let index = entity.index() as usize;
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, EMPTY);
+        }
+
+        let dense_index = self.sparse[index];
+        if dense_index != EMPTY {
+            let dense_index = dense_index as usize;
+            if self.entities[dense_index] == entity {
+                self.ticks[dense_index].changed = tick;
+            } else {
+                // The slot still held a stale generation of the same index.
+                self.entities[dense_index] = entity;
+                self.ticks[dense_index] = ComponentTicks::new(tick);
+            }
+            return Some(std::mem::replace(&mut self.data[dense_index], value));
+        }
+
+        self.sparse[index] = self.data.len() as u32;
+        self.entities.push(entity);
+        self.data.push(value);
+        self.ticks.push(ComponentTicks::new(tick));
+        None
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let dense_index = self.dense_index(entity)?;
+
+        self.sparse[entity.index() as usize] = EMPTY;
+        self.entities.swap_remove(dense_index);
+        let value = self.data.swap_remove(dense_index);
+        self.ticks.swap_remove(dense_index);
+
+        if let Some(moved) = self.entities.get(dense_index) {
+            self.sparse[moved.index() as usize] = dense_index as u32;
+        }
+
+        Some(value)
+    }
+
+    pub fn dense_index(&self, entity: Entity) -> Option<usize> {
+        let dense_index = *self.sparse.get(entity.index() as usize)?;
+        if dense_index == EMPTY || self.entities[dense_index as usize] != entity {
+            return None;
+        }
+        Some(dense_index as usize)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.dense_index(entity).is_some()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.dense_index(entity).map(|dense_index| &self.data[dense_index])
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.dense_index(entity).map(|dense_index| &mut self.data[dense_index])
+    }
+
+    // Mutable access that records the change at `tick`.
+    pub fn get_mut_marked(&mut self, entity: Entity, tick: u64) -> Option<&mut T> {
+        let dense_index = self.dense_index(entity)?;
+        self.ticks[dense_index].changed = tick;
+        Some(&mut self.data[dense_index])
+    }
+
+    pub fn ticks(&self, entity: Entity) -> Option<ComponentTicks> {
+        self.dense_index(entity).map(|dense_index| self.ticks[dense_index])
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.entities.iter().copied().zip(self.data.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.entities.iter().copied().zip(self.data.iter_mut())
+    }
+
+    pub(crate) fn data_ptr(&mut self) -> *mut T {
+        self.data.as_mut_ptr()
+    }
+
+    pub(crate) fn ticks_ptr(&mut self) -> *mut ComponentTicks {
+        self.ticks.as_mut_ptr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_remove_keeps_sparse_indices() {
+        let entities: Vec<Entity> = (0..4).map(|index| Entity::new(index, 0)).collect();
+        let mut set = SparseSet::new();
+        for (value, entity) in entities.iter().enumerate() {
+            set.insert(*entity, value, 0);
+        }
+
+        // The last element is moved into the hole left by the first one
+        assert_eq!(set.remove(entities[0]), Some(0));
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.dense_index(entities[3]), Some(0));
+        assert_eq!(set.entities(), &[entities[3], entities[1], entities[2]]);
+
+        for (value, entity) in entities.iter().enumerate().skip(1) {
+            assert_eq!(set.get(*entity), Some(&value));
+        }
+        assert!(!set.contains(entities[0]));
+
+        // Removing the last dense element moves nothing
+        assert_eq!(set.remove(entities[2]), Some(2));
+        assert_eq!(set.get(entities[3]), Some(&3));
+        assert_eq!(set.get(entities[1]), Some(&1));
+        assert_eq!(set.remove(entities[2]), None);
+    }
+
+    #[test]
+    fn stale_generation_is_not_found() {
+        let mut set = SparseSet::new();
+        let old = Entity::new(5, 0);
+        let new = Entity::new(5, 1);
+
+        set.insert(old, "old", 0);
+        assert_eq!(set.get(new), None);
+        assert_eq!(set.remove(new), None);
+
+        // Inserting the new generation replaces the stale value in place
+        assert_eq!(set.insert(new, "new", 1), Some("old"));
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.get(new), Some(&"new"));
+        assert_eq!(set.get(old), None);
+        assert!(set.ticks(new).unwrap().is_added(0));
+    }
+}
@@ -1,144 +1,241 @@
+use std::os::raw::c_void;
+
 use cgmath::Matrix4;
+use gl::types::*;
 
 use crate::c_str;
 use crate::core::frame_context::FrameContext;
+use crate::core::timer::{Stopwatch, Timer};
+use crate::glutils::buffer::{VertexArray, VertexBuffer};
 use crate::glutils::shader::Shader;
 use crate::graphics::animation::AnimationComponent;
+use crate::graphics::batch::{BatchKey, DrawOrder, SpriteBatcher, SpriteVertex};
+use crate::graphics::layers::RenderLayers;
+use crate::graphics::managers::{AnimationManager, SpritesheetManager};
 use crate::graphics::sprite::SpriteRendererComponent;
-use crate::world::components::{Parent, TransformComponent};
+use crate::world::components::{Children, Parent, TransformComponent, TransformInterpolation};
 use crate::world::entity::Entity;
+use crate::world::query::{Access, With, Without};
+use crate::world::world::World;
 
 #[allow(unused_variables)]
-pub trait System {
+pub trait System: Send {
     fn update(&mut self, ctx: &mut FrameContext) {}
+
+    // Called instead of `update` for systems added to `Stage::FixedUpdate`,
+    // once per fixed step. Use `ctx.time.fixed_delta_time()` there.
+    fn fixed_update(&mut self, ctx: &mut FrameContext) {}
+
     fn render(&mut self, ctx: &mut FrameContext, shader: &Shader) {}
+
+    // Components and resources (including `Events<T>` channels) read and written
+    // by `update` or `fixed_update`. Systems that declare it can run in parallel with the ones they
+    // do not conflict with; `None` keeps the system exclusive on the main thread.
+    fn access(&self) -> Option<Access> {
+        None
+    }
 }
 
 pub struct TransformSystem;
 
 impl System for TransformSystem {
+    fn access(&self) -> Option<Access> {
+        let mut access = Access::new();
+        access.add_read::<Parent>();
+        access.add_read::<Children>();
+        access.add_read::<TransformInterpolation>();
+        access.add_write::<TransformComponent>();
+        Some(access)
+    }
+
     fn update(&mut self, ctx: &mut FrameContext) {
-        let parent_data: Vec<(Entity, Entity)> = {
-            let parents_pool = ctx.world.get_components::<Parent>()
-                .expect("ParentComponent pool not found");
-            let transforms_pool = ctx.world.get_components::<TransformComponent>()
-                .expect("TransformComponent pool not found");
-
-            parents_pool.iter()
-                .filter_map(|(&child_id, parent_comp)| {
-                    if let Some(child_transform) = transforms_pool.get(&child_id) {
-                        if child_transform.transform.is_dirty() {
-                            return Some((child_id, parent_comp.0));
-                        }
+        let detached: Vec<Entity> = ctx.world.removed::<Parent>().iter().collect();
+        // Any entity without a parent is a root, with or without a transform of
+        // its own: its transformed descendants are propagated all the same.
+        let mut roots: Vec<Entity> = ctx.world
+            .query_filtered::<Entity, (With<TransformComponent>, Without<Parent>)>()
+            .collect();
+        roots.extend(ctx.world.query_filtered::<Entity, (With<Children>, Without<TransformComponent>, Without<Parent>)>());
+
+        // Depth-first from every root, so a parent's global matrix is always
+        // up to date before its children are visited.
+        let mut stack: Vec<(Entity, Option<Matrix4<f32>>, bool)> = roots.into_iter()
+            .map(|root| (root, None, false))
+            .collect();
+
+        let alpha = ctx.time.interpolation_alpha();
+
+        while let Some((entity, parent_matrix, parent_changed)) = stack.pop() {
+            // Interpolated entities move between fixed steps, so they are recomputed every frame.
+            let previous = ctx.world.get_component::<TransformInterpolation>(entity)
+                .and_then(|interpolation| interpolation.previous.clone());
+
+            let changed = previous.is_some()
+                || parent_changed
+                || ctx.world.is_changed::<TransformComponent>(entity)
+                || ctx.world.is_changed::<Parent>(entity)
+                || detached.contains(&entity);
+
+            let matrix = if changed {
+                // The model matrix is a cache derived from the transform: updating it
+                // must not make the transform look changed to other systems.
+                ctx.world.get_components_mut::<TransformComponent>().and_then(|transforms| transforms.get_mut(entity)).map(|transform_comp| {
+                    match (&parent_matrix, &previous) {
+                        (_, Some(previous)) => transform_comp.transform.compute_interpolated_model_matrix(parent_matrix.as_ref(), previous, alpha),
+                        (Some(parent_matrix), None) => transform_comp.transform.compute_model_matrix_with_parent(parent_matrix),
+                        (None, None) => transform_comp.transform.compute_model_matrix()
                     }
-                    None
+                    *transform_comp.transform.get_model_matrix()
                 })
-                .collect()
-        };
-
-        let mut transforms_to_update: Vec<Entity> = Vec::new();
-
-        {
-            let transforms_pool = ctx.world.get_components::<TransformComponent>()
-                .expect("TransformComponent pool not found");
-            let parents_pool = ctx.world.get_components::<Parent>()
-                .expect("ParentComponent pool not found");
-            
-            for (entity_id, transform_comp) in transforms_pool.iter() {
-                if transform_comp.transform.is_dirty() && parents_pool.get(entity_id).is_none() {
-                    transforms_to_update.push(*entity_id);
-                }
-            }
-        }
-        
-        {
-            let transforms_pool = ctx.world.get_components_mut::<TransformComponent>()
-                .expect("TransformComponent pool not found");
-            
-            for entity_id in transforms_to_update {
-                if let Some(transform_comp) = transforms_pool.get_mut(&entity_id) {
-                    transform_comp.transform.compute_model_matrix();
-                }
+            } else {
+                ctx.world.get_component::<TransformComponent>(entity)
+                    .map(|transform_comp| *transform_comp.transform.get_model_matrix())
+            };
+
+            // Entities without a transform pass their parent's matrix through.
+            let matrix = matrix.or(parent_matrix);
+
+            if let Some(children) = ctx.world.get_component::<Children>(entity) {
+                stack.extend(children.iter().map(|child| (child, matrix, changed)));
             }
         }
+    }
+}
 
-        let transforms_pool = ctx.world.get_components_mut::<TransformComponent>()
-            .expect("TransformComponent pool not found");
-        
-        for (child_id, parent_id) in parent_data {
-            if let Some(parent_transform) = transforms_pool.get(&parent_id).cloned() {
-                if let Some(child_transform) = transforms_pool.get_mut(&child_id) {
-                     child_transform.transform.compute_model_matrix_with_parent(
-                         &parent_transform.transform.get_model_matrix()
-                     );
-                }
-            }
+// Records the state of interpolated transforms before each fixed step, so
+// rendering can blend from it towards the state the step produces.
+pub struct TransformInterpolationSystem;
+
+impl System for TransformInterpolationSystem {
+    fn access(&self) -> Option<Access> {
+        let mut access = Access::new();
+        access.add_read::<TransformComponent>();
+        access.add_write::<TransformInterpolation>();
+        Some(access)
+    }
+
+    fn fixed_update(&mut self, ctx: &mut FrameContext) {
+        for (transform_comp, mut interpolation) in ctx.world.query::<(&TransformComponent, &mut TransformInterpolation)>() {
+            interpolation.previous = Some(transform_comp.transform.clone());
         }
     }
 }
 
-pub struct SpriteRenderSystem;
+// Draws every sprite through one streaming vertex buffer, back to front in
+// `DrawOrder`, with one draw call per run of sprites sharing a texture.
+pub struct SpriteRenderSystem {
+    batcher: SpriteBatcher,
+    // Created on the first render, once the GL context is current.
+    buffers: Option<(VertexArray, VertexBuffer)>
+}
+
+impl Default for SpriteRenderSystem {
+    fn default() -> Self {
+        SpriteRenderSystem::new()
+    }
+}
+
+impl SpriteRenderSystem {
+    pub fn new() -> Self {
+        SpriteRenderSystem {
+            batcher: SpriteBatcher::new(),
+            buffers: None
+        }
+    }
+
+    fn create_buffers() -> (VertexArray, VertexBuffer) {
+        let vao = VertexArray::new();
+        let vbo = VertexBuffer::new();
+
+        vao.bind();
+        vbo.bind();
+
+        let stride = std::mem::size_of::<SpriteVertex>() as GLsizei;
+        vao.set_attribute(0, 3, gl::FLOAT, stride, std::ptr::null());
+        vao.set_attribute(1, 2, gl::FLOAT, stride, (3 * std::mem::size_of::<GLfloat>()) as *const c_void);
+        vao.set_attribute(2, 4, gl::FLOAT, stride, (5 * std::mem::size_of::<GLfloat>()) as *const c_void);
+
+        vbo.unbind();
+        vao.unbind();
+
+        (vao, vbo)
+    }
+}
 
 impl System for SpriteRenderSystem {
     fn render(&mut self, ctx: &mut FrameContext, shader: &Shader) {
-        let transform_pool = ctx.world.get_components::<TransformComponent>()
-            .expect("TransformComponent pool not found");
-        let sprites_pool = ctx.world.get_components::<SpriteRendererComponent>()
-            .expect("SpriteRendererComponent pool not found");
-
-        for (entity_id, sprite) in sprites_pool.iter() {
-            if let Some(transform_comp) = transform_pool.get(entity_id) {
-                unsafe {
-                    shader.use_program();
-                    sprite.texture.active(0);
-                    sprite.texture.bind();
-                    shader.set_int(c_str!("texture_diffuse1"), 0);
-
-                    let transform_matrix = transform_comp.transform.get_model_matrix();
-
-                    // 2. On crée une matrice pour la taille de base de la texture
-                    let base_size_matrix = Matrix4::from_nonuniform_scale(
-                        sprite.width as f32, 
-                        sprite.height as f32, 
-                        1.0
-                    );
-
-                    let final_model_matrix = transform_matrix * base_size_matrix;
-                    
-                    shader.set_mat4(c_str!("model"), &final_model_matrix);
-
-                    sprite.vao.bind();
-                    gl::DrawArrays(gl::TRIANGLES, 0, 6);
-                    sprite.vao.unbind();
-                }
-            }
+        self.batcher.clear();
+
+        // Without the resource every sprite is drawn as on the default layer
+        let layers = ctx.world.remove_resource::<RenderLayers>();
+        let fallback = RenderLayers::default();
+        let settings = layers.as_ref().unwrap_or(&fallback);
+
+        for (entity, transform_comp, sprite) in ctx.world.query::<(Entity, &TransformComponent, &SpriteRendererComponent)>() {
+            let model_matrix = transform_comp.transform.get_model_matrix();
+
+            let order = DrawOrder {
+                layer: sprite.layer,
+                y_sort: settings.is_y_sorted(sprite.layer).then_some(model_matrix.w.y),
+                z: sprite.z,
+                tie_breaker: entity.index()
+            };
+            let key = BatchKey { shader: shader.id, texture: sprite.texture.id };
+            self.batcher.push(order, key, model_matrix, &sprite.quad());
+        }
+
+        if let Some(layers) = layers {
+            ctx.world.insert_resource(layers);
+        }
+
+        self.batcher.build();
+
+        if self.batcher.batches().is_empty() {
+            return;
         }
 
+        let (vao, vbo) = self.buffers.get_or_insert_with(Self::create_buffers);
+
+        vbo.bind();
+        vbo.set_stream_data(self.batcher.vertices());
+        vbo.unbind();
+
         unsafe {
+            shader.use_program();
+            shader.set_int(c_str!("texture_diffuse1"), 0);
             gl::ActiveTexture(gl::TEXTURE0);
+
+            vao.bind();
+            for batch in self.batcher.batches() {
+                gl::BindTexture(gl::TEXTURE_2D, batch.key.texture);
+                gl::DrawArrays(gl::TRIANGLES, batch.first_vertex as GLint, batch.vertex_count as GLsizei);
+            }
+            vao.unbind();
+
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
     }
 }
 
+// Stays exclusive: it borrows the animation and spritesheet managers through
+// `resource_scope`.
 pub struct AnimationSystem;
 
 impl System for AnimationSystem {
     fn update(&mut self, ctx: &mut FrameContext) {
-        let animation_manager = &ctx.animation_manager;
-        let spritesheet_manager = &ctx.spritesheet_manager;
-
-        let entities_to_update: Vec<Entity> = {
-            ctx.world.get_components::<AnimationComponent>()
-                .map(|pool| pool.keys().copied().collect())
-                .unwrap_or_else(Vec::new)
-        };
+        let delta_time = ctx.time.delta_time();
 
-        for entity_id in entities_to_update {
-            let Some((anim_comp, sprite_comp)) = ctx.world.get_components_mut_pair::<AnimationComponent, SpriteRendererComponent>(entity_id) else {
-                continue;
-            };
+        ctx.world.resource_scope(|world, animation_manager: &mut AnimationManager| {
+            world.resource_scope(|world, spritesheet_manager: &mut SpritesheetManager| {
+                Self::animate(world, animation_manager, spritesheet_manager, delta_time);
+            });
+        });
+    }
+}
 
+impl AnimationSystem {
+    fn animate(world: &mut World, animation_manager: &AnimationManager, spritesheet_manager: &SpritesheetManager, delta_time: f32) {
+        for (mut anim_comp, mut sprite_comp) in world.query::<(&mut AnimationComponent, &mut SpriteRendererComponent)>() {
             if !anim_comp.is_playing || anim_comp.current_animation.is_none() {
                 continue;
             }
@@ -149,7 +246,7 @@ impl System for AnimationSystem {
                 None => continue,
             };
 
-            anim_comp.timer += ctx.time.delta_time();
+            anim_comp.timer += delta_time;
 
             if anim_comp.timer >= animation.frame_duration {
                 anim_comp.timer -= animation.frame_duration;
@@ -168,37 +265,10 @@ impl System for AnimationSystem {
                 let sprite_name = &animation.frames[anim_comp.current_frame_index];
 
                 if let Some(sprite_data) = spritesheet.get_sprite(sprite_name) {
-                    let positions: [f32; 12] = [
-                        -0.5,  0.5, -0.5, -0.5,  0.5, -0.5,
-                        -0.5,  0.5,  0.5, -0.5,  0.5,  0.5,
-                    ];
-
-                    let mut tex_coords = sprite_data.tex_coords;
-                    if animation.flipped {
-                        let u_val1 = tex_coords[0]; 
-                        let u_val2 = *tex_coords.iter().step_by(2).find(|&&u| u != u_val1).unwrap_or(&u_val1);
-
-                        for i in 0..6 {
-                            if tex_coords[i * 2] == u_val1 {
-                                tex_coords[i * 2] = u_val2;
-                            } else {
-                                tex_coords[i * 2] = u_val1;
-                            }
-                        }
-                    }
-
-                    let mut new_vertices = [0.0f32; 24];
-                    for i in 0..6 {
-                        new_vertices[i * 4]     = positions[i * 2];
-                        new_vertices[i * 4 + 1] = positions[i * 2 + 1];
-                        new_vertices[i * 4 + 2] = tex_coords[i * 2];
-                        new_vertices[i * 4 + 3] = tex_coords[i * 2 + 1];
-                    }
-
-                    sprite_comp.vbo.bind();
-                    sprite_comp.vbo.set_data(&new_vertices);
-                    sprite_comp.vbo.unbind();
-
+                    // The frame replaces whatever part of the texture was shown.
+                    sprite_comp.tex_coords = sprite_data.tex_coords;
+                    sprite_comp.source_rect = None;
+                    sprite_comp.flip_x = animation.flipped;
                     sprite_comp.width = sprite_data.width;
                     sprite_comp.height = sprite_data.height;
                 }
@@ -206,3 +276,27 @@ impl System for AnimationSystem {
         }
     }
 }
+
+// Ticks every `Timer` and `Stopwatch` component with the scaled delta time.
+pub struct TimerSystem;
+
+impl System for TimerSystem {
+    fn access(&self) -> Option<Access> {
+        let mut access = Access::new();
+        access.add_write::<Timer>();
+        access.add_write::<Stopwatch>();
+        Some(access)
+    }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        let delta_time = ctx.time.delta_time();
+
+        for mut timer in ctx.world.query::<&mut Timer>() {
+            timer.tick(delta_time);
+        }
+
+        for mut stopwatch in ctx.world.query::<&mut Stopwatch>() {
+            stopwatch.tick(delta_time);
+        }
+    }
+}
@@ -1,18 +1,33 @@
 use hashbrown::HashMap;
 use std::any::{Any, TypeId};
+use std::marker::PhantomData;
+use crate::world::change_detection::RemovedComponents;
 use crate::world::components::Component;
-use crate::world::entity::Entity;
+use crate::world::entity::{Entity, EntityAllocator, EntityReserver};
+use crate::world::events::Events;
+use crate::world::query::{Access, Query, QueryFilter, WorldQuery};
+use crate::world::storage::SparseSet;
 
-trait IComponentMap: Any + Send + Sync {
-    fn remove_entity(&mut self, entity: Entity);
+pub(crate) trait IComponentMap: Any + Send + Sync {
+    fn remove_entity(&mut self, entity: Entity) -> bool;
+    fn len(&self) -> usize;
+    fn entities(&self) -> &[Entity];
 
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
-impl<T: Component + 'static + Send + Sync> IComponentMap for HashMap<Entity, T> {
-    fn remove_entity(&mut self, entity: Entity) {
-        self.remove(&entity);
+impl<T: Component + 'static + Send + Sync> IComponentMap for SparseSet<T> {
+    fn remove_entity(&mut self, entity: Entity) -> bool {
+        self.remove(entity).is_some()
+    }
+
+    fn len(&self) -> usize {
+        SparseSet::len(self)
+    }
+
+    fn entities(&self) -> &[Entity] {
+        SparseSet::entities(self)
     }
     
     fn as_any(&self) -> &dyn Any {
@@ -25,98 +40,434 @@ impl<T: Component + 'static + Send + Sync> IComponentMap for HashMap<Entity, T>
 }
 
 pub struct World {
-    next_entity_id: u32,
-    components: HashMap<TypeId, Box<dyn IComponentMap>>
+    entities: EntityAllocator,
+    components: HashMap<TypeId, Box<dyn IComponentMap>>,
+    resources: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    event_updaters: Vec<fn(&mut World)>,
+    // Bumped before every system run; components are stamped with it when
+    // inserted or mutated. `last_change_tick` is the last run of the current system.
+    change_tick: u64,
+    last_change_tick: u64,
+    removed: HashMap<TypeId, Vec<(Entity, u64)>>,
+    removed_trim_tick: u64,
+    // Set on the views handed to systems running in parallel (see `split`):
+    // storages and resources that were not moved into the view are read from
+    // the main world, which nothing writes to until the batch is merged back.
+    parent: Option<*const World>,
+    // What the system of a view declared writing: the only storages and
+    // resources the view may create, since `merge` moves them as a whole.
+    writes: Vec<TypeId>
 }
 
+// SAFETY: only views created by `split` carry a `parent` pointer, and the main
+// world is exclusively borrowed by the scheduler, which never writes to it while
+// those views are alive. Everything else stored in the world is `Send + Sync`.
+unsafe impl Send for World {}
+unsafe impl Sync for World {}
+
 impl World {
     pub fn new() -> Self {
         World {
-            next_entity_id: 0,
-            components: HashMap::new()
+            entities: EntityAllocator::new(),
+            components: HashMap::new(),
+            resources: HashMap::new(),
+            event_updaters: Vec::new(),
+            change_tick: 1,
+            last_change_tick: 0,
+            removed: HashMap::new(),
+            removed_trim_tick: 0,
+            parent: None,
+            writes: Vec::new()
         }
     }
 
+    fn parent(&self) -> Option<&World> {
+        // SAFETY: see the `Send`/`Sync` impls above.
+        self.parent.map(|parent| unsafe { &*parent })
+    }
+
+    fn allocator(&self) -> &EntityAllocator {
+        match self.parent() {
+            Some(parent) => parent.allocator(),
+            None => &self.entities
+        }
+    }
+
+    fn can_write(&self, type_id: TypeId) -> bool {
+        self.parent.is_none() || self.writes.contains(&type_id)
+    }
+
     pub fn new_entity(&mut self) -> Entity {
-        let entity = Entity(self.next_entity_id);
-        self.next_entity_id += 1;
-        entity
+        assert!(self.parent.is_none(), "Systems running in parallel must spawn entities through Commands");
+        self.entities.allocate()
     }
     
+    // Hands out ids that become alive on the next `flush_entities`.
+    pub fn entity_reserver(&self) -> EntityReserver {
+        self.allocator().reserver()
+    }
+
+    pub fn flush_entities(&mut self) {
+        assert!(self.parent.is_none(), "Reserved entities are flushed on the main world");
+        for entity in self.entities.flush() {
+            self.remove_entity(entity);
+        }
+    }
+
     pub fn remove_entity(&mut self, entity: Entity) {
-        for component_map in self.components.values_mut() {
-            component_map.remove_entity(entity);
+        assert!(self.parent.is_none(), "Systems running in parallel must despawn entities through Commands");
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        self.detach_from_hierarchy(entity);
+        self.entities.free(entity);
+
+        for (type_id, component_map) in self.components.iter_mut() {
+            if component_map.remove_entity(entity) {
+                self.removed.entry(*type_id).or_default().push((entity, self.change_tick));
+            }
         }
     }
 
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.allocator().is_alive(entity)
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.allocator().alive_count()
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.allocator().iter()
+    }
+
     pub fn register_component<T: Component + 'static + Send + Sync>(&mut self) {
         let type_id = TypeId::of::<T>();
-        self.components.entry(type_id).or_insert_with(|| Box::new(HashMap::<Entity, T>::new()));
+        if !self.can_write(type_id) {
+            return;
+        }
+        self.components.entry(type_id).or_insert_with(|| Box::new(SparseSet::<T>::new()));
     }
 
     pub fn add_component<T: Component + 'static + Send + Sync>(&mut self, entity: Entity, component: T) {
+        if !self.is_alive(entity) {
+            eprintln!("Cannot add a component to dead entity {:?}", entity);
+            return;
+        }
+
         let type_id = TypeId::of::<T>();
+        if !self.can_write(type_id) {
+            eprintln!("Cannot add {} from a system that does not declare write access to it", std::any::type_name::<T>());
+            return;
+        }
+
         let components_of_type = self.components
             .entry(type_id)
-            .or_insert_with(|| Box::new(HashMap::<Entity, T>::new()));
+            .or_insert_with(|| Box::new(SparseSet::<T>::new()));
 
         let components_of_type_map = components_of_type
             .as_any_mut()
-            .downcast_mut::<HashMap<Entity, T>>()
+            .downcast_mut::<SparseSet<T>>()
             .expect("Erreur de downcast de composant");
 
-        components_of_type_map.insert(entity, component);
+        components_of_type_map.insert(entity, component, self.change_tick);
+    }
+
+    pub fn remove_component<T: Component + 'static>(&mut self, entity: Entity) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let tick = self.change_tick;
+        let component = self.get_components_mut::<T>()
+            .and_then(|components_map| components_map.remove(entity))?;
+        self.removed.entry(TypeId::of::<T>()).or_default().push((entity, tick));
+        self.unlink_removed(entity, &component);
+        Some(component)
     }
 
     pub fn get_component<T: Component + 'static>(&self, entity: Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
         self.get_components::<T>()
-            .and_then(|components_map| components_map.get(&entity))
+            .and_then(|components_map| components_map.get(entity))
     }
 
+    // Counts as a change of the component, whether or not it is actually written.
     pub fn get_component_mut<T: Component + 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let tick = self.change_tick;
         self.get_components_mut::<T>()
-            .and_then(|components_map| components_map.get_mut(&entity))
+            .and_then(|components_map| components_map.get_mut_marked(entity, tick))
+    }
+
+    pub fn is_added<T: Component + 'static>(&self, entity: Entity) -> bool {
+        self.get_components::<T>()
+            .and_then(|components_map| components_map.ticks(entity))
+            .is_some_and(|ticks| ticks.is_added(self.last_change_tick))
     }
 
-    pub fn get_components<T: Component + 'static>(&self) -> Option<&HashMap<Entity, T>> {
+    pub fn is_changed<T: Component + 'static>(&self, entity: Entity) -> bool {
+        self.get_components::<T>()
+            .and_then(|components_map| components_map.ticks(entity))
+            .is_some_and(|ticks| ticks.is_changed(self.last_change_tick))
+    }
+
+    pub fn removed<T: Component + 'static>(&self) -> RemovedComponents<'_, T> {
+        let type_id = TypeId::of::<T>();
+        let removed = [Some(self), self.parent()].into_iter()
+            .flatten()
+            .filter_map(|world| world.removed.get(&type_id))
+            .map(|removed| removed.as_slice())
+            .collect();
+
+        RemovedComponents {
+            removed,
+            last_run: self.last_change_tick,
+            _marker: PhantomData
+        }
+    }
+
+    pub fn get_components<T: Component + 'static>(&self) -> Option<&SparseSet<T>> {
         let type_id = TypeId::of::<T>();
         self.components
             .get(&type_id)
-            .and_then(|box_map| box_map.as_any().downcast_ref::<HashMap<Entity, T>>())
+            .or_else(|| self.parent().and_then(|parent| parent.components.get(&type_id)))
+            .and_then(|box_map| box_map.as_any().downcast_ref::<SparseSet<T>>())
     }
 
-    pub fn get_components_mut<T: Component + 'static>(&mut self) -> Option<&mut HashMap<Entity, T>> {
+    // Direct storage access bypasses change detection.
+    pub fn get_components_mut<T: Component + 'static>(&mut self) -> Option<&mut SparseSet<T>> {
         let type_id = TypeId::of::<T>();
         self.components
             .get_mut(&type_id)
-            .and_then(|box_map| box_map.as_any_mut().downcast_mut::<HashMap<Entity, T>>())
+            .and_then(|box_map| box_map.as_any_mut().downcast_mut::<SparseSet<T>>())
     }
     
-    pub fn get_components_mut_pair<T, U>(&mut self, entity: Entity) -> Option<(&mut T, &mut U)>
-    where
-        T: Component + 'static + Send + Sync,
-        U: Component + 'static + Send + Sync,
-    {
-        let type_id_t = TypeId::of::<T>();
-        let type_id_u = TypeId::of::<U>();
-
-        if type_id_t == type_id_u {
-            return None;
+    pub fn change_tick(&self) -> u64 {
+        self.change_tick
+    }
+
+    pub fn last_change_tick(&self) -> u64 {
+        self.last_change_tick
+    }
+
+    // Starts a system run and returns the tick its changes are stamped with.
+    pub(crate) fn begin_system_run(&mut self, last_run: u64) -> u64 {
+        self.change_tick += 1;
+        self.last_change_tick = last_run;
+        self.change_tick
+    }
+
+    // Moves past the tick of the system that just ran, so changes made after it
+    // (by later systems or outside the schedule) are new to it on its next run.
+    pub(crate) fn end_system_run(&mut self) {
+        self.change_tick += 1;
+    }
+
+    pub(crate) fn set_run_ticks(&mut self, last_run: u64, this_run: u64) {
+        self.last_change_tick = last_run;
+        self.change_tick = this_run;
+    }
+
+    // Forgets removals older than the previous call, once per frame.
+    pub fn update_removed_components(&mut self) {
+        let trim_tick = self.removed_trim_tick;
+        for removed in self.removed.values_mut() {
+            removed.retain(|(_, tick)| *tick > trim_tick);
+        }
+        self.removed_trim_tick = self.change_tick;
+    }
+
+    pub fn query<Q: WorldQuery>(&mut self) -> Query<'_, Q> {
+        Query::new(self)
+    }
+
+    pub fn query_filtered<Q: WorldQuery, F: QueryFilter>(&mut self) -> Query<'_, Q, F> {
+        Query::new(self)
+    }
+
+    pub(crate) fn storage_ptr<T: Component + 'static>(&mut self) -> Option<*mut SparseSet<T>> {
+        self.get_components_mut::<T>().map(|components_map| components_map as *mut _)
+    }
+
+    pub(crate) fn storage_ptr_read<T: Component + 'static>(&self) -> Option<*const SparseSet<T>> {
+        self.get_components::<T>().map(|components_map| components_map as *const _)
+    }
+
+    pub fn insert_resource<T: Any + Send + Sync>(&mut self, resource: T) {
+        if !self.can_write(TypeId::of::<T>()) {
+            eprintln!("Cannot insert {} from a system that does not declare write access to it", std::any::type_name::<T>());
+            return;
         }
+        self.resources.insert(TypeId::of::<T>(), Box::new(resource));
+    }
+
+    pub fn remove_resource<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .and_then(|resource| resource.downcast::<T>().ok())
+            .map(|resource| *resource)
+    }
+
+    pub fn contains_resource<T: Any + Send + Sync>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+            || self.parent().is_some_and(|parent| parent.contains_resource::<T>())
+    }
 
-        let components_ptr: *mut _ = &mut self.components;
-        
-        unsafe {
-            let map_t_box = (*components_ptr).get_mut(&type_id_t)?;
-            let map_u_box = (*components_ptr).get_mut(&type_id_u)?;
-            
-            let map_t = map_t_box.as_any_mut().downcast_mut::<HashMap<Entity, T>>()?;
-            let map_u = map_u_box.as_any_mut().downcast_mut::<HashMap<Entity, U>>()?;
-            
-            let comp_t = map_t.get_mut(&entity)?;
-            let comp_u = map_u.get_mut(&entity)?;
+    pub fn resource<T: Any + Send + Sync>(&self) -> Option<&T> {
+        let type_id = TypeId::of::<T>();
+        self.resources
+            .get(&type_id)
+            .or_else(|| self.parent().and_then(|parent| parent.resources.get(&type_id)))
+            .and_then(|resource| resource.downcast_ref::<T>())
+    }
 
-            Some((comp_t, comp_u))
+    pub fn resource_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|resource| resource.downcast_mut::<T>())
+    }
+
+    // Takes the resource out of the world for the duration of `f`, so that it can
+    // be used alongside queries that borrow the world mutably.
+    pub fn resource_scope<T: Any + Send + Sync, R>(&mut self, f: impl FnOnce(&mut World, &mut T) -> R) -> Option<R> {
+        let mut resource = self.remove_resource::<T>()?;
+        let result = f(self, &mut resource);
+        self.insert_resource(resource);
+        Some(result)
+    }
+
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) {
+        if self.contains_resource::<Events<T>>() {
+            return;
         }
+
+        self.insert_resource(Events::<T>::new());
+        self.event_updaters.push(|world| {
+            if let Some(events) = world.resource_mut::<Events<T>>() {
+                events.update();
+            }
+        });
+    }
+
+    pub fn send_event<T: Send + Sync + 'static>(&mut self, event: T) {
+        match self.resource_mut::<Events<T>>() {
+            Some(events) => events.send(event),
+            None => eprintln!("Event type {} was sent but never registered", std::any::type_name::<T>())
+        }
+    }
+
+    // Swaps the buffers of every registered event channel, once per frame.
+    pub fn update_events(&mut self) {
+        let updaters = self.event_updaters.clone();
+        for update in updaters {
+            update(self);
+        }
+    }
+
+    // Moves the storages and resources each access set writes into its own view
+    // of this world, so the systems owning them can run on other threads.
+    pub(crate) fn split(&mut self, accesses: &[&Access]) -> Vec<World> {
+        let mut views: Vec<World> = accesses.iter().map(|access| {
+            let mut view = World::new();
+            view.writes = access.writes().collect();
+            for type_id in access.writes() {
+                if let Some(storage) = self.components.remove(&type_id) {
+                    view.components.insert(type_id, storage);
+                }
+                if let Some(resource) = self.resources.remove(&type_id) {
+                    view.resources.insert(type_id, resource);
+                }
+            }
+            view
+        }).collect();
+
+        let parent = self as *const World;
+        for view in views.iter_mut() {
+            view.parent = Some(parent);
+        }
+        views
+    }
+
+    // Puts back what `split` moved out, along with anything the view created.
+    pub(crate) fn merge(&mut self, view: World) {
+        for (type_id, storage) in view.components {
+            let replaced = self.components.insert(type_id, storage);
+            assert!(replaced.is_none(), "A storage was written by two systems of the same batch");
+        }
+        for (type_id, resource) in view.resources {
+            let replaced = self.resources.insert(type_id, resource);
+            assert!(replaced.is_none(), "A resource was written by two systems of the same batch");
+        }
+        for (type_id, removed) in view.removed {
+            self.removed.entry(type_id).or_default().extend(removed);
+        }
+        self.change_tick = self.change_tick.max(view.change_tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+    impl Component for Velocity {}
+
+    fn writing<T: 'static>() -> Access {
+        let mut access = Access::new();
+        access.add_write::<T>();
+        access
+    }
+
+    #[test]
+    fn views_create_only_declared_storages() {
+        let mut world = World::new();
+        let entity = world.new_entity();
+        let (positions, velocities) = (writing::<Position>(), writing::<Velocity>());
+
+        let mut views = world.split(&[&positions, &velocities]);
+        views[0].add_component(entity, Position(1));
+        views[1].add_component(entity, Velocity(2));
+        // Undeclared: would replace the storage of the first view on merge
+        views[1].add_component(entity, Position(3));
+        views[1].insert_resource(7u32);
+
+        for view in views {
+            world.merge(view);
+        }
+
+        assert_eq!(world.get_component::<Position>(entity), Some(&Position(1)));
+        assert_eq!(world.get_component::<Velocity>(entity), Some(&Velocity(2)));
+        assert!(world.resource::<u32>().is_none());
+    }
+
+    #[test]
+    fn storages_moved_to_a_view_come_back() {
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.add_component(entity, Position(1));
+        world.insert_resource(1u32);
+
+        let mut access = writing::<Position>();
+        access.add_write::<u32>();
+        let mut views = world.split(&[&access]);
+        views[0].get_component_mut::<Position>(entity).unwrap().0 = 2;
+        *views[0].resource_mut::<u32>().unwrap() = 2;
+        world.merge(views.pop().unwrap());
+
+        assert_eq!(world.get_component::<Position>(entity), Some(&Position(2)));
+        assert_eq!(world.resource::<u32>(), Some(&2));
     }
 }
@@ -0,0 +1,50 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index};
+
+// Implements `engine::world::bundle::Bundle` for a struct by inserting each of
+// its fields in declaration order. Every field must itself be a bundle, so
+// components and other derived bundles can both be used.
+#[proc_macro_derive(Bundle)]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return syn::Error::new_spanned(&input.ident, "Bundle can only be derived for structs")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let inserts = match fields {
+        Fields::Named(fields) => fields.named.iter()
+            .map(|field| {
+                let member = field.ident.as_ref().unwrap();
+                quote! { ::engine::world::bundle::Bundle::insert_into(self.#member, world, entity); }
+            })
+            .collect::<Vec<_>>(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|index| {
+                let member = Index::from(index);
+                quote! { ::engine::world::bundle::Bundle::insert_into(self.#member, world, entity); }
+            })
+            .collect::<Vec<_>>(),
+        Fields::Unit => Vec::new()
+    };
+
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let expanded = quote! {
+        impl #impl_generics ::engine::world::bundle::Bundle for #name #type_generics #where_clause {
+            #[allow(unused_variables)]
+            fn insert_into(self, world: &mut ::engine::world::world::World, entity: ::engine::world::entity::Entity) {
+                #(#inserts)*
+            }
+        }
+    };
+
+    expanded.into()
+}
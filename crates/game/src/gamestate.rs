@@ -1,10 +1,11 @@
-use engine::world::components::Component;
+use engine::world::world::World;
 use uuid::Uuid;
 
-pub struct GameStateComponent {
+pub struct GameState {
     pub player_id: Option<Uuid>
 }
 
-impl Component for GameStateComponent {
-    
+// Run condition for systems that only make sense once the server has assigned us an id.
+pub fn is_connected(world: &World) -> bool {
+    world.resource::<GameState>().is_some_and(|gamestate| gamestate.player_id.is_some())
 }
@@ -0,0 +1,18 @@
+use engine::core::{application::ApplicationBuilder, plugin::Plugin};
+
+pub struct NetworkConfig {
+    pub server_addr: String
+}
+
+// Makes the server address available to the scenes that open the session.
+pub struct NetworkPlugin {
+    pub server_addr: String
+}
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut ApplicationBuilder) {
+        app.world_mut().insert_resource(NetworkConfig {
+            server_addr: self.server_addr.clone()
+        });
+    }
+}
@@ -1,9 +1,9 @@
 use cgmath::Vector3;
 use common::player::{Direction, State, PlayerInfo};
-use engine::{core::frame_context::FrameContext, graphics::{animation::AnimationComponent, sprite::SpriteCreator}, world::components::TransformComponent};
+use engine::{core::frame_context::FrameContext, world::components::TransformInterpolation};
 use uuid::Uuid;
 
-use crate::{gamestate::GameStateComponent, network::{event::NetworkEvent, handlers::handler::Handler}, player::DistantPlayerComponent};
+use crate::{gamestate::GameState, network::{event::NetworkEvent, handlers::handler::Handler}, player::{player_actor, DistantPlayerBundle, DistantPlayerComponent}};
 
 pub struct ConnectedHandler;
 
@@ -14,13 +14,11 @@ impl Handler for ConnectedHandler {
                 Ok(uuid) => {
                     println!("✅ Received and parsed player UUID: {}", uuid);
 
-                    if let Some(gamestates_map) = ctx.world.get_components_mut::<GameStateComponent>() {
-                        if let Some(gamestate) = gamestates_map.values_mut().next() {
-                            gamestate.player_id = Some(uuid);
-                            println!("Player UUID {} stored in GameStateComponent.", uuid);
-                        } else {
-                            eprintln!("❌ No GameStateComponent found in the world to update.");
-                        }
+                    if let Some(gamestate) = ctx.world.resource_mut::<GameState>() {
+                        gamestate.player_id = Some(uuid);
+                        println!("Player UUID {} stored in GameState.", uuid);
+                    } else {
+                        eprintln!("❌ No GameState resource found in the world to update.");
                     }
                 }
                 Err(e) => {
@@ -36,11 +34,6 @@ impl Handler for ConnectedHandler {
                 Ok(players) => {
                     for player_data in players {
                         if let Ok(player_id) = Uuid::parse_str(&player_data.id) {
-                            let distant_player_entity = ctx.world.new_entity();
-
-                            let mut transform_comp = TransformComponent::new();
-                            transform_comp.transform.set_local_position(Vector3::new(player_data.x, player_data.y, player_data.z));
-
                             let distant_player_component = DistantPlayerComponent {
                                 player_id,
                                 speed: 100.0,
@@ -49,13 +42,12 @@ impl Handler for ConnectedHandler {
                                 target_position: None
                             };
 
-                            let mut anim_comp = AnimationComponent::new();
-                            anim_comp.play("player_base_idle_down");
-
-                            ctx.world.add_component(distant_player_entity, transform_comp);
-                            ctx.world.add_component(distant_player_entity, distant_player_component);
-                            ctx.world.add_component(distant_player_entity, SpriteCreator::from_sprite(ctx.spritesheet_manager.get("player_base").unwrap(), "idle_down_0").unwrap());
-                            ctx.world.add_component(distant_player_entity, anim_comp);
+                            match player_actor(ctx.world, Vector3::new(player_data.x, player_data.y, player_data.z)) {
+                                Some(actor) => {
+                                    ctx.commands.spawn_bundle(DistantPlayerBundle { actor, player: distant_player_component, interpolation: TransformInterpolation::new() });
+                                }
+                                None => eprintln!("❌ Missing 'player_base' spritesheet, cannot spawn distant player {}", player_id)
+                            }
                         }
                     }
                 }
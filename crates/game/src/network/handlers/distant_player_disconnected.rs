@@ -10,10 +10,9 @@ impl Handler for DistantPlayerDisconnectedHandler {
             match Uuid::parse_str(player_id_string) {
                 Ok(player_id) => {
                     if let Some(distant_player_comps) = ctx.world.get_components::<DistantPlayerComponent>() {
-                        for (entity, distant_player_comp) in distant_player_comps {
+                        for (entity, distant_player_comp) in distant_player_comps.iter() {
                             if distant_player_comp.player_id == player_id {
-                                ctx.world.remove_entity(*entity);
-                                break;
+                                ctx.commands.despawn_recursive(entity);
                             }
                         }
                     }
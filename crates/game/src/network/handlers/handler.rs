@@ -1,6 +1,6 @@
 use engine::core::frame_context::FrameContext;
 use crate::network::event::NetworkEvent;
 
-pub trait Handler {
+pub trait Handler: Send {
     fn handle(&self, ctx: &mut FrameContext, event: NetworkEvent);
 }
\ No newline at end of file
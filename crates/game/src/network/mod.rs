@@ -1,4 +1,6 @@
 pub mod client;
+pub mod config;
 pub mod event;
 pub mod system;
-pub mod handlers;
\ No newline at end of file
+pub mod handlers;
+pub mod session;
\ No newline at end of file
@@ -0,0 +1,44 @@
+use common::message::Message;
+use tokio::sync::mpsc;
+
+use crate::network::{client::Client, event::NetworkEvent};
+
+// Lance la tâche réseau en arrière-plan pour un client déjà connecté.
+// Renvoie l'émetteur des messages du jeu et le récepteur des événements du serveur.
+pub fn start_session(mut client: Client) -> (mpsc::Sender<Message>, mpsc::Receiver<NetworkEvent>) {
+    let (game_tx, mut network_rx) = mpsc::channel::<Message>(100);
+    let (network_tx, game_rx) = mpsc::channel::<NetworkEvent>(100);
+
+    tokio::spawn(async move {
+        loop {
+            // tokio::select! attend que l'une des deux opérations se termine
+            tokio::select! {
+                // Cas A: On reçoit un message du jeu à envoyer au serveur
+                Some(message_to_send) = network_rx.recv() => {
+                    if let Err(e) = client.send(&message_to_send).await {
+                        eprintln!("Erreur lors de l'envoi du message: {}", e);
+                        break; // On arrête la boucle en cas d'erreur
+                    }
+                },
+
+                // Cas B: On reçoit un message du serveur
+                result = client.receive() => {
+                     match result {
+                        Ok(message) => {
+                            let event = NetworkEvent { data: message.get_data() };
+                            if let Err(e) = network_tx.send(event).await {
+                                eprintln!("Impossible d'envoyer l'événement au jeu: {}", e);
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("Erreur de réception: {}", e);
+                            break; // Sortir de la boucle si la connexion est perdue
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    (game_tx, game_rx)
+}
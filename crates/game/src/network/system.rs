@@ -25,6 +25,8 @@ impl System for NetworkEventSystem {
             if let Some(action) = event.data.get("action") {
                 if let Some(handler) = self.handlers.get(action) {
                     handler.handle(ctx, event);
+                    // Later events of the same batch may target entities spawned by this one.
+                    ctx.apply_commands();
                 }
                 else {
                     eprintln!("Handler for action '{}' not found.", action);
@@ -1,9 +1,11 @@
 use cgmath::{vec3, InnerSpace, Vector2, Vector3};
 use common::player::{Direction, State};
-use engine::{graphics::animation::AnimationComponent, world::{components::{Component, TransformComponent}, entity::Entity, system::System}};
+use engine::{core::{application::ApplicationBuilder, frame_context::FrameContext, plugin::{AnimationPlugin, Plugin}}, graphics::{animation::AnimationComponent, bundles::SpriteActorBundle, layers::RenderLayers, managers::{AnimationManager, SpritesheetManager}}, world::prefab::ComponentRegistry, world::{bundle::Bundle, components::{Component, TransformComponent, TransformInterpolation}, entity::Entity, events::{EventReader, Events}, query::Access, system::System, world::World}};
 use glfw::Key;
+use serde::Deserialize;
 use uuid::Uuid;
 
+#[derive(Deserialize)]
 pub struct LocalPlayerComponent {
     pub speed: f32,
     pub direction: Direction,
@@ -12,73 +14,147 @@ pub struct LocalPlayerComponent {
 
 impl Component for LocalPlayerComponent {}
 
+// Player components, events and assets. The player systems themselves belong
+// to the game scene.
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut ApplicationBuilder) {
+        // Les sprites et animations du joueur
+        app.add_plugin(AnimationPlugin);
+
+        let world = app.world_mut();
+        world.register_component::<LocalPlayerComponent>();
+        world.add_event::<PlayerStateChanged>();
+        world.resource_mut::<ComponentRegistry>().unwrap().register::<LocalPlayerComponent>("local_player");
+
+        let Some(spritesheet_manager) = world.resource_mut::<SpritesheetManager>() else {
+            eprintln!("PlayerPlugin needs a window, the player assets are not loaded");
+            return;
+        };
+        spritesheet_manager.load("resources/data/spritesheets/player_base.json").unwrap();
+
+        let animation_manager = world.resource_mut::<AnimationManager>().unwrap();
+
+        // player base idle animation
+        animation_manager.load("resources/data/animations/player_base_idle_down.json").unwrap();
+        animation_manager.load("resources/data/animations/player_base_idle_left.json").unwrap();
+        animation_manager.load("resources/data/animations/player_base_idle_right.json").unwrap();
+        animation_manager.load("resources/data/animations/player_base_idle_up.json").unwrap();
+
+        // player base walk animation
+        animation_manager.load("resources/data/animations/player_base_walk_down.json").unwrap();
+        animation_manager.load("resources/data/animations/player_base_walk_left.json").unwrap();
+        animation_manager.load("resources/data/animations/player_base_walk_right.json").unwrap();
+        animation_manager.load("resources/data/animations/player_base_walk_up.json").unwrap();
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct PlayerStateChanged {
+    pub entity: Entity,
+    pub direction: Direction,
+    pub state: State
+}
+
 pub struct LocalPlayerSystem;
 
 impl System for LocalPlayerSystem {
+    fn access(&self) -> Option<Access> {
+        let mut access = Access::new();
+        access.add_write::<LocalPlayerComponent>();
+        access.add_write::<TransformComponent>();
+        access.add_write::<Events<PlayerStateChanged>>();
+        Some(access)
+    }
+
     #[allow(unused_assignments)]
-    fn update(&mut self, ctx: &mut engine::core::frame_context::FrameContext) {
-        let mut target_entities: Vec<Entity> = Vec::new();
-        if let (Some(players), Some(transforms)) = (
-            ctx.world.get_components::<LocalPlayerComponent>(),
-            ctx.world.get_components::<TransformComponent>(),
-        ) {
-            for (entity, _player) in players.iter() {
-                if transforms.contains_key(entity) {
-                    target_entities.push(*entity);
-                }
+    fn fixed_update(&mut self, ctx: &mut FrameContext) {
+        let mut state_changes = Vec::new();
+        let query = ctx.world.query::<(Entity, &mut LocalPlayerComponent, &mut TransformComponent)>();
+
+        for (entity, mut player_component, mut transform_component) in query {
+            let mut velocity = Vector2::new(0.0, 0.0);
+            let mut direction = player_component.direction.clone();
+            let mut state = player_component.state.clone();
+            
+            if ctx.input.is_key_pressed(Key::W) {
+                velocity.y = 1.0;
+                direction = Direction::UP;
+            }
+            if ctx.input.is_key_pressed(Key::S) {
+                velocity.y = -1.0;
+                direction = Direction::DOWN;
+            }
+
+            if ctx.input.is_key_pressed(Key::A) {
+                velocity.x = -1.0;
+                direction = Direction::LEFT;
+            }
+            if ctx.input.is_key_pressed(Key::D) {
+                velocity.x = 1.0;
+                direction = Direction::RIGHT;
+            }
+            
+            if velocity.magnitude2() > 0.0 {
+                let final_movement = velocity.normalize() * player_component.speed * ctx.time.fixed_delta_time();
+
+                let position = *transform_component.transform.get_local_position();
+                transform_component.transform.set_local_position(vec3(
+                    position.x + final_movement.x, 
+                    position.y + final_movement.y, 
+                    position.z
+                ));
+
+                state = State::WALK;
+            }
+            else {
+                state = State::IDLE;
+            }
+
+            if direction != player_component.direction || state != player_component.state {
+                player_component.direction = direction;
+                player_component.state = state;
+                state_changes.push(PlayerStateChanged { entity, direction, state });
             }
         }
 
-        for entity in target_entities {
-            if let Some((player_component, transform_component)) = ctx
-                .world
-                .get_components_mut_pair::<LocalPlayerComponent, TransformComponent>(entity)
-            {
-                let mut velocity = Vector2::new(0.0, 0.0);
-                let mut direction = player_component.direction.clone();
-                let mut state = player_component.state.clone();
-                
-                if ctx.input.is_key_pressed(Key::W) {
-                    velocity.y = 1.0;
-                    direction = Direction::UP;
-                }
-                if ctx.input.is_key_pressed(Key::S) {
-                    velocity.y = -1.0;
-                    direction = Direction::DOWN;
-                }
+        for state_change in state_changes {
+            ctx.send_event(state_change);
+        }
+    }
+}
 
-                if ctx.input.is_key_pressed(Key::A) {
-                    velocity.x = -1.0;
-                    direction = Direction::LEFT;
-                }
-                if ctx.input.is_key_pressed(Key::D) {
-                    velocity.x = 1.0;
-                    direction = Direction::RIGHT;
-                }
-                
-                if velocity.magnitude2() > 0.0 {
-                    let final_movement = velocity.normalize() * player_component.speed * ctx.time.delta_time();
-
-                    let position = transform_component.transform.get_local_position();
-                    transform_component.transform.set_local_position(vec3(
-                        position.x + final_movement.x, 
-                        position.y + final_movement.y, 
-                        position.z
-                    ));
-
-                    state = State::WALK;
-                }
-                else {
-                    state = State::IDLE;
-                }
+pub struct PlayerAnimationSystem {
+    state_changes: EventReader<PlayerStateChanged>
+}
 
-                if direction != player_component.direction || state != player_component.state {
-                    player_component.direction = direction;
-                    player_component.state = state;
-                    if let Some(animation_comp) = ctx.world.get_component_mut::<AnimationComponent>(entity) {
-                        animation_comp.play(format!("player_base_{}_{}", State::to_str(state), Direction::to_str(direction)).as_str());
-                    }
-                }
+impl PlayerAnimationSystem {
+    pub fn new() -> Self {
+        Self {
+            state_changes: EventReader::new()
+        }
+    }
+}
+
+impl System for PlayerAnimationSystem {
+    fn access(&self) -> Option<Access> {
+        let mut access = Access::new();
+        access.add_read::<Events<PlayerStateChanged>>();
+        access.add_write::<AnimationComponent>();
+        Some(access)
+    }
+
+    fn update(&mut self, ctx: &mut FrameContext) {
+        let state_changes: Vec<PlayerStateChanged> = ctx.read_events(&mut self.state_changes)
+            .into_iter()
+            .copied()
+            .collect();
+
+        for state_change in state_changes {
+            if let Some(animation_comp) = ctx.world.get_component_mut::<AnimationComponent>(state_change.entity) {
+                let anim_name = format!("player_base_{}_{}", State::to_str(state_change.state), Direction::to_str(state_change.direction));
+                animation_comp.play(&anim_name);
             }
         }
     }
@@ -94,33 +170,48 @@ pub struct DistantPlayerComponent {
 
 impl Component for DistantPlayerComponent {}
 
+#[derive(Bundle)]
+pub struct DistantPlayerBundle {
+    pub actor: SpriteActorBundle,
+    pub player: DistantPlayerComponent,
+    pub interpolation: TransformInterpolation
+}
+
+// Player sprite standing at `position`, idle and facing down.
+pub fn player_actor(world: &World, position: Vector3<f32>) -> Option<SpriteActorBundle> {
+    let spritesheet = world.resource::<SpritesheetManager>()?.get("player_base")?;
+    let mut actor = SpriteActorBundle::from_sprite(spritesheet, "idle_down_0", position, "player_base_idle_down")?;
+    actor.sprite.layer = world.resource::<RenderLayers>()?.get("world")?;
+    Some(actor)
+}
+
 pub struct DistantPlayerSystem;
 
 #[allow(unused_assignments)]
 impl System for DistantPlayerSystem {
-    fn update(&mut self, ctx: &mut engine::core::frame_context::FrameContext) {
-        let mut target_entities: Vec<Entity> = Vec::new();
-        if let Some(distant_players) = ctx.world.get_components::<DistantPlayerComponent>() {
-            target_entities.extend(distant_players.keys().copied());
-        }
+    fn access(&self) -> Option<Access> {
+        let mut access = Access::new();
+        access.add_write::<DistantPlayerComponent>();
+        access.add_write::<TransformComponent>();
+        Some(access)
+    }
+
+    fn fixed_update(&mut self, ctx: &mut FrameContext) {
+        for (mut distant_player_comp, mut transform_comp) in ctx.world.query::<(&mut DistantPlayerComponent, &mut TransformComponent)>() {
+            if let Some(target_pos) = distant_player_comp.target_position {
+                let current_pos = *transform_comp.transform.get_local_position();
+                let direction_vector = target_pos - current_pos;
+                let distance_to_target = direction_vector.magnitude();
+
+                let max_move_this_frame = distant_player_comp.speed * ctx.time.fixed_delta_time();
 
-        for entity in target_entities {
-            if let Some((distant_player_comp, transform_comp)) = ctx.world.get_components_mut_pair::<DistantPlayerComponent, TransformComponent>(entity) {
-                if let Some(target_pos) = distant_player_comp.target_position {
-                    let current_pos = transform_comp.transform.get_local_position();
-                    let direction_vector = target_pos - current_pos;
-                    let distance_to_target = direction_vector.magnitude();
-
-                    let max_move_this_frame = distant_player_comp.speed * ctx.time.delta_time();
-
-                    if distance_to_target <= max_move_this_frame {
-                        transform_comp.transform.set_local_position(target_pos);
-                        distant_player_comp.target_position = None;
-                    }
-                    else {
-                        let movement = direction_vector.normalize() * max_move_this_frame;
-                        transform_comp.transform.set_local_position(current_pos + movement);
-                    }
+                if distance_to_target <= max_move_this_frame {
+                    transform_comp.transform.set_local_position(target_pos);
+                    distant_player_comp.target_position = None;
+                }
+                else {
+                    let movement = direction_vector.normalize() * max_move_this_frame;
+                    transform_comp.transform.set_local_position(current_pos + movement);
                 }
             }
         }
@@ -0,0 +1,206 @@
+use common::message::Message;
+use engine::{core::{scene::{Scene, SceneContext, SceneTransition}, timer::{Timer, TimerMode}}, graphics::postprocess::{PassParam, PostProcessStack}, world::{entity::Entity, query::With, schedule::{IntoSystemConfig, Schedule, Stage}, world::World}};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    gamestate::{is_connected, GameState},
+    network::{
+        client::Client,
+        config::NetworkConfig,
+        event::NetworkEvent,
+        handlers::{
+            connected_handler::ConnectedHandler,
+            distant_player_disconnected::DistantPlayerDisconnectedHandler,
+            distant_player_moved::{DistantPlayerMovedHandler, NewDistantPlayerHandler}
+        },
+        session::start_session,
+        system::NetworkEventSystem
+    },
+    player::{DistantPlayerComponent, DistantPlayerSystem, LocalPlayerSystem, PlayerAnimationSystem},
+    tick::TickSystem
+};
+
+const RETRY_DELAY: f32 = 3.0;
+const FADE_IN_DURATION: f32 = 0.5;
+
+// Écran affiché tant que la connexion au serveur n'est pas établie.
+pub struct ConnectScene {
+    connection: Option<oneshot::Receiver<Result<Client, String>>>,
+    retry_timer: Timer,
+    screen: Option<Entity>
+}
+
+impl ConnectScene {
+    pub fn new() -> Self {
+        Self {
+            connection: None,
+            retry_timer: Timer::new(RETRY_DELAY, TimerMode::Once),
+            screen: None
+        }
+    }
+
+    fn start_connection(&mut self, world: &World) {
+        let Some(server_addr) = world.resource::<NetworkConfig>().map(|config| config.server_addr.clone()) else {
+            eprintln!("❌ No NetworkConfig resource found in the world.");
+            return;
+        };
+
+        let (connection_tx, connection_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let result = Client::connect(&server_addr).await.map_err(|e| e.to_string());
+            let _ = connection_tx.send(result);
+        });
+
+        self.connection = Some(connection_rx);
+    }
+}
+
+impl Scene for ConnectScene {
+    fn name(&self) -> &str {
+        "connect"
+    }
+
+    fn on_enter(&mut self, ctx: &mut SceneContext) {
+        match ctx.world.spawn_prefab("connect_screen") {
+            Ok(screen) => self.screen = Some(screen),
+            Err(e) => eprintln!("❌ {}", e)
+        }
+
+        self.start_connection(ctx.world);
+    }
+
+    fn on_exit(&mut self, ctx: &mut SceneContext) {
+        if let Some(screen) = self.screen.take() {
+            ctx.world.remove_entity(screen);
+        }
+    }
+
+    fn update(&mut self, ctx: &mut SceneContext) -> SceneTransition {
+        let Some(connection) = &mut self.connection else {
+            // Temps réel : l'écran de connexion ne dépend pas de la pause du jeu.
+            if self.retry_timer.tick(ctx.time.unscaled_delta_time()).just_finished() {
+                self.start_connection(ctx.world);
+            }
+            return SceneTransition::None;
+        };
+
+        match connection.try_recv() {
+            Ok(Ok(client)) => {
+                println!("Connexion réussie !");
+                let (game_tx, game_rx) = start_session(client);
+                return SceneTransition::Switch(Box::new(GameScene::new(game_tx, game_rx)));
+            }
+            Ok(Err(e)) => {
+                eprintln!("Impossible de se connecter au serveur: {}. Nouvelle tentative dans {}s", e, RETRY_DELAY);
+                self.connection = None;
+                self.retry_timer.reset();
+            }
+            Err(oneshot::error::TryRecvError::Empty) => {}
+            Err(oneshot::error::TryRecvError::Closed) => {
+                self.connection = None;
+                self.retry_timer.reset();
+            }
+        }
+
+        SceneTransition::None
+    }
+}
+
+// La partie en ligne, une fois la session réseau ouverte.
+pub struct GameScene {
+    game_tx: mpsc::Sender<Message>,
+    game_rx: Option<mpsc::Receiver<NetworkEvent>>,
+    fade_in: Timer,
+    // Le décor et le joueur local, retirés en quittant la partie.
+    entities: Vec<Entity>
+}
+
+impl GameScene {
+    pub fn new(game_tx: mpsc::Sender<Message>, game_rx: mpsc::Receiver<NetworkEvent>) -> Self {
+        Self {
+            game_tx,
+            game_rx: Some(game_rx),
+            fade_in: Timer::new(FADE_IN_DURATION, TimerMode::Once),
+            entities: Vec::new()
+        }
+    }
+}
+
+impl Scene for GameScene {
+    fn name(&self) -> &str {
+        "game"
+    }
+
+    fn build(&mut self, schedule: &mut Schedule) {
+        if let Some(game_rx) = self.game_rx.take() {
+            let mut network_system = NetworkEventSystem::new(game_rx);
+            network_system.handlers.insert("connected".to_string(), Box::new(ConnectedHandler));
+            network_system.handlers.insert("player_moved".to_string(), Box::new(DistantPlayerMovedHandler));
+            network_system.handlers.insert("new_distant_player".to_string(), Box::new(NewDistantPlayerHandler));
+            network_system.handlers.insert("player_disconnected".to_string(), Box::new(DistantPlayerDisconnectedHandler));
+
+            schedule.add_system(Stage::PreUpdate, network_system.label("network"));
+        }
+
+        schedule
+            .add_system(Stage::FixedUpdate, LocalPlayerSystem.label("local_player"))
+            .add_system(Stage::FixedUpdate, DistantPlayerSystem.label("distant_player"))
+            .add_system(Stage::FixedUpdate, TickSystem::new(self.game_tx.clone()).after("local_player").run_if(is_connected))
+            .add_system(Stage::Update, PlayerAnimationSystem::new());
+    }
+
+    fn on_enter(&mut self, ctx: &mut SceneContext) {
+        ctx.world.insert_resource(GameState { player_id: None });
+
+        match ctx.world.spawn_prefab("container") {
+            Ok(container) => self.entities.push(container),
+            Err(e) => eprintln!("❌ {}", e)
+        }
+
+        match ctx.world.spawn_prefab("player") {
+            Ok(player_entity) => {
+                self.entities.push(player_entity);
+                ctx.camera.target = Some(player_entity);
+            }
+            Err(e) => eprintln!("❌ {}", e)
+        }
+
+        // La partie apparaît depuis un écran noir
+        if let Some(post_process) = ctx.world.resource_mut::<PostProcessStack>() {
+            post_process.set_param("fade", "amount", PassParam::Float(1.0));
+            post_process.set_enabled("fade", true);
+        }
+        self.fade_in.reset();
+    }
+
+    fn update(&mut self, ctx: &mut SceneContext) -> SceneTransition {
+        if self.fade_in.finished() {
+            return SceneTransition::None;
+        }
+
+        self.fade_in.tick(ctx.time.unscaled_delta_time());
+        if let Some(post_process) = ctx.world.resource_mut::<PostProcessStack>() {
+            post_process.set_param("fade", "amount", PassParam::Float(1.0 - self.fade_in.fraction()));
+            if self.fade_in.finished() {
+                post_process.set_enabled("fade", false);
+            }
+        }
+
+        SceneTransition::None
+    }
+
+    fn on_exit(&mut self, ctx: &mut SceneContext) {
+        ctx.camera.target = None;
+        ctx.world.remove_resource::<GameState>();
+
+        for entity in self.entities.drain(..) {
+            ctx.world.despawn_recursive(entity);
+        }
+
+        // Les joueurs distants sont créés par les handlers réseau
+        let distant_players: Vec<Entity> = ctx.world.query_filtered::<Entity, With<DistantPlayerComponent>>().collect();
+        for entity in distant_players {
+            ctx.world.despawn_recursive(entity);
+        }
+    }
+}
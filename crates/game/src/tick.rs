@@ -1,66 +1,61 @@
 use common::message::Message;
 use common::player::{Direction, State};
 use engine::{
-    core::frame_context::FrameContext, 
+    core::{frame_context::FrameContext, timer::{Timer, TimerMode}},
     world::{
-        components::TransformComponent, entity::Entity, system::System
+        components::TransformComponent, query::Access, system::System
     }
 };
 use tokio::sync::mpsc;
-use crate::{gamestate::GameStateComponent, player::LocalPlayerComponent};
+use crate::{gamestate::GameState, player::LocalPlayerComponent};
 
 const TICK_INVERTAL: f32 = 1.0 / 20.0;
 
 pub struct TickSystem {
     network_tx: mpsc::Sender<Message>,
-    tick_timer: f32
+    tick_timer: Timer
 }
 
 impl TickSystem {
     pub fn new(network_tx: mpsc::Sender<Message>) -> Self {
         Self {
             network_tx,
-            tick_timer: 0.0
+            tick_timer: Timer::new(TICK_INVERTAL, TimerMode::Repeating)
         }
     }
 }
 
 impl System for TickSystem {
-    fn update(&mut self, ctx: &mut FrameContext) {
-        let delta_time = ctx.time.delta_time();
-        self.tick_timer += delta_time;
-
-        if self.tick_timer >= TICK_INVERTAL {
-            self.tick_timer -= TICK_INVERTAL;
+    fn access(&self) -> Option<Access> {
+        let mut access = Access::new();
+        access.add_read::<GameState>();
+        access.add_read::<LocalPlayerComponent>();
+        access.add_read::<TransformComponent>();
+        Some(access)
+    }
 
-            if let Some(player_comp) = ctx.world.get_components::<LocalPlayerComponent>() {
-                let player_entities: Vec<Entity> = player_comp.keys().copied().collect();
+    fn fixed_update(&mut self, ctx: &mut FrameContext) {
+        self.tick_timer.tick(ctx.time.fixed_delta_time());
 
-                for entity in player_entities {
-                    if let (Some(player), Some(transform), Some(gamestates)) = (
-                        ctx.world.get_component::<LocalPlayerComponent>(entity),
-                        ctx.world.get_component::<TransformComponent>(entity),
-                        ctx.world.get_components::<GameStateComponent>()
-                    ) {
-                        if let Some(gamestate) = gamestates.values().next() {
-                            let position = transform.transform.get_local_position();
+        if self.tick_timer.just_finished() {
+            let Some(player_id) = ctx.world.resource::<GameState>().and_then(|gamestate| gamestate.player_id) else {
+                return;
+            };
 
-                            let mut msg = Message::new();
-                            msg.add("player_id", &gamestate.player_id.unwrap().to_string());
-                            msg.add("action", "player_move");
-                            msg.add("x", &position.x.to_string());
-                            msg.add("y", &position.y.to_string());
-                            msg.add("z", &position.z.to_string());
-                            msg.add("direction", Direction::to_str(player.direction));
-                            msg.add("state", State::to_str(player.state));
+            if let Some((player, transform)) = ctx.world.query::<(&LocalPlayerComponent, &TransformComponent)>().next() {
+                let position = transform.transform.get_local_position();
 
-                            if let Err(e) = self.network_tx.try_send(msg) {
-                                eprintln!("Impossible d'envoyer la mise à jour du tick au réseau: {}", e);
-                            }
+                let mut msg = Message::new();
+                msg.add("player_id", &player_id.to_string());
+                msg.add("action", "player_move");
+                msg.add("x", &position.x.to_string());
+                msg.add("y", &position.y.to_string());
+                msg.add("z", &position.z.to_string());
+                msg.add("direction", Direction::to_str(player.direction));
+                msg.add("state", State::to_str(player.state));
 
-                            break;
-                        }
-                    }
+                if let Err(e) = self.network_tx.try_send(msg) {
+                    eprintln!("Impossible d'envoyer la mise à jour du tick au réseau: {}", e);
                 }
             }
         }
@@ -3,6 +3,8 @@ use common::player::PlayerInfo;
 use engine::world::components::TransformComponent;
 
 use axum::extract::State;
+use axum::http::{header, StatusCode};
+use engine::world::snapshot::SnapshotFormat;
 
 use askama::Template;
 use crate::player::PlayerComponent;
@@ -21,7 +23,7 @@ pub async fn show_players_page(State(server): State<Arc<ElyriaServer>>) -> Playe
 
     if let Some(player_components) = world.get_components::<PlayerComponent>() {
         for (entity, player_comp) in player_components.iter() {
-            if let Some(transform_comp) = world.get_component::<TransformComponent>(*entity) {
+            if let Some(transform_comp) = world.get_component::<TransformComponent>(entity) {
                 players_info.push(PlayerInfo {
                     id: player_comp.id.to_string(),
                     x: transform_comp.transform.get_local_position().x,
@@ -36,4 +38,14 @@ pub async fn show_players_page(State(server): State<Arc<ElyriaServer>>) -> Playe
         title: "Liste des joueurs connectés".to_string(),
         players: players_info
     }
-}
\ No newline at end of file
+}
+
+// Current world state as a JSON snapshot, for debugging and golden files.
+pub async fn show_snapshot(State(server): State<Arc<ElyriaServer>>) -> (StatusCode, [(header::HeaderName, &'static str); 1], Vec<u8>) {
+    let world = server.world.lock().await;
+
+    match world.save_snapshot(SnapshotFormat::Json) {
+        Ok(snapshot) => (StatusCode::OK, [(header::CONTENT_TYPE, "application/json")], snapshot),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, [(header::CONTENT_TYPE, "text/plain")], e.to_string().into_bytes())
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use common::player::PlayerInfo;
 use engine::world::components::TransformComponent;
+use engine::world::snapshot::SnapshotRegistry;
 use engine::world::world::World;
 use tokio::net::tcp::OwnedWriteHalf;
 use tokio::sync::Mutex;
@@ -12,7 +13,7 @@ use common::message::Message;
 use uuid::Uuid;
 
 use crate::handler::{MessageHandler, MessageHandlerContext};
-use crate::player::PlayerComponent;
+use crate::player::{PlayerBundle, PlayerComponent};
 
 pub struct ElyriaServer {
     pub handlers: Mutex<HashMap<String, Box<dyn MessageHandler + Send + Sync>>>,
@@ -22,9 +23,16 @@ pub struct ElyriaServer {
 
 impl ElyriaServer {
     pub fn new() -> Self {
+        let mut world = World::new();
+
+        let mut snapshot_registry = SnapshotRegistry::new();
+        snapshot_registry.register::<TransformComponent>("transform");
+        snapshot_registry.register::<PlayerComponent>("player");
+        world.insert_resource(snapshot_registry);
+
         Self {
             handlers: Mutex::new(HashMap::new()),
-            world: Mutex::new(World::new()),
+            world: Mutex::new(world),
             clients: Mutex::new(HashMap::new())
         }
     }
@@ -46,14 +54,11 @@ impl ElyriaServer {
         
         {
             let mut world = self.world.lock().await;
-            let player_entity = world.new_entity();
-            player_entity_id = player_entity;
-
             let transform = TransformComponent::new();
             initial_transform = transform.clone();
 
-            world.add_component(player_entity, transform);
-            world.add_component(player_entity, PlayerComponent::new(player_id));
+            let player_entity = world.spawn(PlayerBundle { transform, player: PlayerComponent::new(player_id) });
+            player_entity_id = player_entity;
 
             println!("Joueur créé avec l'ID: {}, Entité: {:?}", player_id, player_entity);
             println!("Composants joueurs actuels: {:?}", world.get_components::<PlayerComponent>());
@@ -71,7 +76,7 @@ impl ElyriaServer {
                             continue;
                         }
 
-                        if let Some(transform_comp) = world.get_component::<TransformComponent>(*entity) {
+                        if let Some(transform_comp) = world.get_component::<TransformComponent>(entity) {
                             existing_players_info.push(PlayerInfo {
                                 id: player_comp.id.to_string(),
                                 x: transform_comp.transform.get_local_position().x,
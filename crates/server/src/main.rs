@@ -14,7 +14,7 @@ use axum::{
 use std::net::SocketAddr;
 use crate::player::{PlayerComponent, PlayerMoveHandler};
 use crate::core::server::ElyriaServer;
-use crate::admin::pages::show_players_page;
+use crate::admin::pages::{show_players_page, show_snapshot};
 
 
 #[tokio::main]
@@ -33,6 +33,7 @@ async fn main() {
 
     let app = Router::new()
         .route("/", get(show_players_page))
+        .route("/snapshot", get(show_snapshot))
         .with_state(server);
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
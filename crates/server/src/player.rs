@@ -1,13 +1,14 @@
 use async_trait::async_trait;
 use cgmath::vec3;
 use common::{message::Message, player::{Direction, State}};
-use engine::world::{components::{Component, TransformComponent}, entity::Entity};
+use engine::world::{bundle::Bundle, components::{Component, TransformComponent}};
+use serde::{Deserialize, Serialize};
 use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
 use crate::handler::{MessageHandler, MessageHandlerContext};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PlayerComponent {
     pub id: Uuid,
     pub direction: Direction,
@@ -26,6 +27,12 @@ impl PlayerComponent {
 
 impl Component for PlayerComponent {}
 
+#[derive(Bundle)]
+pub struct PlayerBundle {
+    pub transform: TransformComponent,
+    pub player: PlayerComponent
+}
+
 pub struct PlayerMoveHandler;
 
 #[async_trait]
@@ -41,65 +48,54 @@ impl MessageHandler for PlayerMoveHandler {
             }
         };
 
-        let player_entity: Option<Entity> = {
-            if let Some(player_comps) = ctx.world.get_components::<PlayerComponent>() {
-                player_comps
-                    .iter()
-                    .find(|(_entity, component)| component.id == target_player_id)
-                    .map(|(entity, _component)| *entity)
-            }
-            else {
-                None
-            }
-        };
-        
-        if let Some(entity) = player_entity {
-            let x_pos = data.get("x").and_then(|val| val.parse::<f32>().ok());
-            let y_pos = data.get("y").and_then(|val| val.parse::<f32>().ok());
-            let z_pos = data.get("z").and_then(|val| val.parse::<f32>().ok());
+        let x_pos = data.get("x").and_then(|val| val.parse::<f32>().ok());
+        let y_pos = data.get("y").and_then(|val| val.parse::<f32>().ok());
+        let z_pos = data.get("z").and_then(|val| val.parse::<f32>().ok());
+
+        if let (Some(x), Some(y), Some(z)) = (x_pos, y_pos, z_pos) {
+            let player = ctx.world
+                .query::<(&mut PlayerComponent, &mut TransformComponent)>()
+                .find(|(player_comp, _)| player_comp.id == target_player_id);
+
+            let Some((mut player_comp, mut transform_comp)) = player else {
+                return;
+            };
+
             let state = data.get("state").and_then(|val| val.parse::<State>().ok()).unwrap();
             let direction = data.get("direction").and_then(|val| val.parse::<Direction>().ok()).unwrap();
 
-            if let (Some(x), Some(y), Some(z)) = (x_pos, y_pos, z_pos) {
-                if let Some((player_comp, transform_comp)) = ctx.world.get_components_mut_pair::<PlayerComponent, TransformComponent>(entity) {
-                    transform_comp.transform.set_local_position(vec3(x, y, z));
-                    player_comp.state = state;
-                    player_comp.direction = direction;
-                }
-                else {
-                    return;
-                }
+            transform_comp.transform.set_local_position(vec3(x, y, z));
+            player_comp.state = state;
+            player_comp.direction = direction;
+
+            let mut broadcast_message = Message::new();
+            broadcast_message.add("action", "player_moved");
+            broadcast_message.add("player_id", &target_player_id.to_string());
+            broadcast_message.add("x", &x.to_string());
+            broadcast_message.add("y", &y.to_string());
+            broadcast_message.add("z", &z.to_string());
+            broadcast_message.add("direction", Direction::to_str(direction));
+            broadcast_message.add("state", State::to_str(state));
+
+            let bytes = match broadcast_message.to_bytes() {
+                Ok(b) => b,
+                Err(_) => return
+            };
+
+            let clients_map = ctx.clients.lock().await;
+            for (id, client_writer) in clients_map.iter() {
+                if *id != ctx.current_player_id {
+                    let owned_id = *id;
+                    let writer_arc = client_writer.clone();
+                    let bytes_clone = bytes.clone();
 
-                let mut broadcast_message = Message::new();
-                broadcast_message.add("action", "player_moved");
-                broadcast_message.add("player_id", &target_player_id.to_string());
-                broadcast_message.add("x", &x.to_string());
-                broadcast_message.add("y", &y.to_string());
-                broadcast_message.add("z", &z.to_string());
-                broadcast_message.add("direction", Direction::to_str(direction));
-                broadcast_message.add("state", State::to_str(state));
-
-                let bytes = match broadcast_message.to_bytes() {
-                    Ok(b) => b,
-                    Err(_) => return
-                };
-
-                let clients_map = ctx.clients.lock().await;
-                for (id, client_writer) in clients_map.iter() {
-                    if *id != ctx.current_player_id {
-                        let owned_id = *id;
-                        let writer_arc = client_writer.clone();
-                        let bytes_clone = bytes.clone();
-
-                        tokio::spawn(async move {
-                            let mut writer = writer_arc.lock().await;
-                            if writer.write_u32(bytes_clone.len() as u32).await.is_ok() {
-                                if let Err(e) = writer.write_all(&bytes_clone).await {
-                                    eprintln!("Erreur de broadcast vers le client {}: {}", owned_id, e);
-                                }
-                            }
-                        });
-                    }
+                    tokio::spawn(async move {
+                        let mut writer = writer_arc.lock().await;
+                        if writer.write_u32(bytes_clone.len() as u32).await.is_ok()
+                            && let Err(e) = writer.write_all(&bytes_clone).await {
+                            eprintln!("Erreur de broadcast vers le client {}: {}", owned_id, e);
+                        }
+                    });
                 }
             }
         }
@@ -0,0 +1,24 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screen_texture;
+
+uniform float brightness;
+uniform float contrast;
+uniform float saturation;
+uniform vec3 tint;
+
+void main()
+{
+    vec4 color = texture(screen_texture, TexCoords);
+
+    vec3 graded = color.rgb + brightness;
+    graded = (graded - 0.5) * contrast + 0.5;
+
+    float luminance = dot(graded, vec3(0.2126, 0.7152, 0.0722));
+    graded = mix(vec3(luminance), graded, saturation);
+
+    FragColor = vec4(clamp(graded * tint, 0.0, 1.0), color.a);
+}
@@ -0,0 +1,33 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screen_texture;
+uniform vec2 resolution;
+uniform float time;
+
+uniform float curvature;
+uniform float scanline_intensity;
+uniform float flicker;
+
+void main()
+{
+    // Écran bombé : les coordonnées s'éloignent du centre vers les bords
+    vec2 uv = TexCoords * 2.0 - 1.0;
+    uv *= 1.0 + curvature * dot(uv.yx, uv.yx);
+    uv = uv * 0.5 + 0.5;
+
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec3 color = texture(screen_texture, uv).rgb;
+
+    float scanline = sin(uv.y * resolution.y * 3.14159);
+    color *= 1.0 - scanline_intensity * (0.5 - 0.5 * scanline);
+    color *= 1.0 - flicker * (0.5 + 0.5 * sin(time * 60.0));
+
+    FragColor = vec4(color, 1.0);
+}
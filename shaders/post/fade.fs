@@ -0,0 +1,16 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screen_texture;
+
+// 0 : image intacte, 1 : entièrement recouverte par `color`
+uniform float amount;
+uniform vec3 color;
+
+void main()
+{
+    vec4 screen = texture(screen_texture, TexCoords);
+    FragColor = vec4(mix(screen.rgb, color, clamp(amount, 0.0, 1.0)), screen.a);
+}
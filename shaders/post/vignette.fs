@@ -0,0 +1,23 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screen_texture;
+uniform vec2 resolution;
+
+// Distance au centre où l'assombrissement commence, et sur quelle largeur
+uniform float radius;
+uniform float softness;
+uniform float intensity;
+
+void main()
+{
+    vec4 color = texture(screen_texture, TexCoords);
+
+    // Corrige le rapport d'aspect pour garder une vignette ronde
+    vec2 position = (TexCoords - 0.5) * vec2(resolution.x / resolution.y, 1.0);
+    float vignette = smoothstep(radius, radius - softness, length(position));
+
+    FragColor = vec4(mix(color.rgb, color.rgb * vignette, intensity), color.a);
+}
@@ -2,11 +2,12 @@
 out vec4 FragColor;
 
 in vec2 TexCoords;
+in vec4 Color;
 
 // texture samplers
 uniform sampler2D texture_diffuse1;
 
 void main()
 {
-    FragColor = texture(texture_diffuse1, TexCoords);
-}
\ No newline at end of file
+    FragColor = texture(texture_diffuse1, TexCoords) * Color;
+}
@@ -1,23 +1,25 @@
 #version 330 core
 
-// Attributs fournis par le VBO du SpriteCreator
-layout (location = 0) in vec2 aPos;      // Position en 2D (x, y)
+// Attributs fournis par le VBO du SpriteRenderSystem, déjà en coordonnées monde
+layout (location = 0) in vec3 aPos;      // Position (x, y, z)
 layout (location = 1) in vec2 aTexCoord; // Coordonnées de texture (u, v)
+layout (location = 2) in vec4 aColor;    // Teinte et opacité du sprite
 
 // Sortie vers le Fragment Shader
 out vec2 TexCoords;
+out vec4 Color;
 
 // Matrices de transformation
-uniform mat4 model;
 uniform mat4 view;
 uniform mat4 projection;
 
 void main()
 {
-    // On passe les coordonnées de texture au fragment shader
+    // On passe les coordonnées de texture et la teinte au fragment shader
     TexCoords = aTexCoord;
-    
-    // On transforme la position 2D en une position 3D (z=0)
-    // puis en coordonnées d'écran via les matrices.
-    gl_Position = projection * view * model * vec4(aPos, 0.0, 1.0);
-}
\ No newline at end of file
+    Color = aColor;
+
+    // Les sommets sont transformés sur le CPU par le batcher, il ne reste
+    // que la vue et la projection.
+    gl_Position = projection * view * vec4(aPos, 1.0);
+}